@@ -0,0 +1,179 @@
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, path::Path};
+
+const DEFAULT_CONFIG_PATH: &str = "freitool.yaml";
+const DEFAULT_PROFILE: &str = "default";
+
+/// The `freitool.yaml` configuration file. Every value can be overridden by the
+/// matching command line argument.
+///
+/// ```yaml
+/// profiles:
+///   default:
+///     ios:
+///       key_path: /path/to/AuthKey_XXXX.p8
+///       key_id: XXXX
+///       issuer_id: 00000000-0000-0000-0000-000000000000
+///       key_type: team # or individual, without issuer_id
+///       app_id: "1234567890"
+///       vendor_number: "80000000"
+///     android:
+///       key_path: /path/to/key.json
+///       package_name: com.example.app
+///       track: production
+///       token_cache: /path/to/tokens.json
+/// retry:
+///   max_retries: 3
+///   base_delay_ms: 500
+///   max_delay_ms: 30000
+/// http:
+///   connect_timeout_secs: 10
+///   timeout_secs: 300
+///   proxy: http://proxy.example.com:3128
+///   ca_bundle: /path/to/ca.pem
+/// endpoints:
+///   app_store_connect: https://api.appstoreconnect.apple.com/v1
+///   android_publisher: https://www.googleapis.com/androidpublisher/v3
+///   oauth_token: https://oauth2.googleapis.com/token
+///   play_reporting: https://playdeveloperreporting.googleapis.com/v1beta1
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    #[serde(default)]
+    pub endpoints: EndpointsConfig,
+}
+
+/// The base URLs of the APIs, each falling back to its `FREITOOL_*_URL`
+/// environment variable, then to the real API.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct EndpointsConfig {
+    pub app_store_connect: Option<String>,
+    pub android_publisher: Option<String>,
+    pub oauth_token: Option<String>,
+    pub play_reporting: Option<String>,
+}
+
+/// The HTTP client settings, shared by every profile.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct HttpConfig {
+    pub connect_timeout_secs: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    /// Falls back to `FREITOOL_PROXY`, then the usual `HTTPS_PROXY` variables.
+    pub proxy: Option<String>,
+    /// Falls back to `FREITOOL_CA_BUNDLE`.
+    pub ca_bundle: Option<String>,
+}
+
+/// How transient failures of the API calls are retried, shared by every profile.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct RetryConfig {
+    pub max_retries: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct Profile {
+    #[serde(default)]
+    pub ios: IosProfile,
+
+    #[serde(default)]
+    pub android: AndroidProfile,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct IosProfile {
+    pub key_path: Option<String>,
+    pub key_id: Option<String>,
+    /// Absent for individual keys.
+    pub issuer_id: Option<String>,
+
+    #[serde(default)]
+    pub key_type: KeyType,
+    pub app_id: Option<String>,
+    pub vendor_number: Option<String>,
+}
+
+/// The kind of App Store Connect API key. Team keys are signed with an issuer
+/// ID, individual keys without.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    #[default]
+    Team,
+    Individual,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct AndroidProfile {
+    pub key_path: Option<String>,
+    pub package_name: Option<String>,
+    pub track: Option<String>,
+    /// Reuses the access tokens between invocations when set, see `--token-cache`.
+    pub token_cache: Option<String>,
+}
+
+impl Config {
+    /// Loads the configuration from `path`, `FREITOOL_CONFIG` or `./freitool.yaml`,
+    /// in that order. A missing default file results in an empty configuration.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let path = path
+            .map(|p| p.to_string())
+            .or_else(|| env::var("FREITOOL_CONFIG").ok());
+
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => DEFAULT_CONFIG_PATH.to_string(),
+            None => return Ok(Config::default()),
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+
+        return serde_yaml::from_str(&content)
+            .map_err(|e| format!("Failed to parse config file {}: {}", path, e));
+    }
+
+    /// Returns the name of the profile given by `name` or `FREITOOL_PROFILE`, or
+    /// every profile name when none is given.
+    pub fn profile_names(&self, name: Option<&str>) -> Vec<String> {
+        if let Some(name) = name
+            .map(|n| n.to_string())
+            .or_else(|| env::var("FREITOOL_PROFILE").ok())
+        {
+            return vec![name];
+        }
+
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+
+        if names.is_empty() {
+            names.push(DEFAULT_PROFILE.to_string());
+        }
+
+        return names;
+    }
+
+    /// Returns the profile named `name`, `FREITOOL_PROFILE` or `default`, in that order.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        let name = name
+            .map(|n| n.to_string())
+            .or_else(|| env::var("FREITOOL_PROFILE").ok())
+            .unwrap_or(DEFAULT_PROFILE.to_string());
+
+        match self.profiles.get(&name) {
+            Some(profile) => return Ok(profile.clone()),
+            None if name == DEFAULT_PROFILE => return Ok(Profile::default()),
+            None => return Err(format!("Profile not found: {}", name)),
+        }
+    }
+}
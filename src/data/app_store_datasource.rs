@@ -1,16 +1,21 @@
-use super::datasource::ResponseMapper;
-use reqwest::header::CONTENT_TYPE;
+use super::{
+    datasource::{response_error, ResponseMapper},
+    endpoints::endpoints,
+    http::{client, RetryingRequest},
+    report::Report,
+};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map};
+use std::{fs, io, path::Path};
 
+/// The App Store Connect API, called with a signed token.
 pub struct AppStoreDataSource {
     token: String,
 }
 
-const APP_STORE_CONNECT_URL: &str = "https://api.appstoreconnect.apple.com/v1";
-
-fn ep(endpoint: &str) -> String {
-    format!("{}/{}", APP_STORE_CONNECT_URL, endpoint)
+pub(super) fn ep(endpoint: &str) -> String {
+    format!("{}/{}", endpoints().app_store_connect, endpoint)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,6 +28,14 @@ pub struct AppStoreSingleData<T> {
     pub data: T,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppAttributes {
+    pub name: String,
+
+    #[serde(rename = "bundleId")]
+    pub bundle_id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppStoreVersionData {
     #[serde(rename = "type")]
@@ -51,36 +64,206 @@ pub struct AppStoreVersionLocalizationData<T> {
     pub attributes: T,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppStoreResource<T> {
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub id: String,
+    pub attributes: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildAttributes {
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BetaBuildLocalizationAttributes {
+    pub locale: String,
+    #[serde(rename = "whatsNew")]
+    pub whats_new: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BetaGroupAttributes {
+    pub name: String,
+    #[serde(rename = "isInternalGroup")]
+    pub is_internal_group: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BetaTesterAttributes {
+    pub email: Option<String>,
+    #[serde(rename = "firstName")]
+    pub first_name: Option<String>,
+    #[serde(rename = "lastName")]
+    pub last_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppStoreResourceId {
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppStoreRelationship {
+    pub data: Option<AppStoreResourceId>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct AppStoreLinks {
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerReviewAttributes {
+    pub rating: u8,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    #[serde(rename = "reviewerNickname")]
+    pub reviewer_nickname: Option<String>,
+    #[serde(rename = "createdDate")]
+    pub created_date: String,
+    pub territory: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CustomerReviewRelationships {
+    pub response: Option<AppStoreRelationship>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerReview {
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub id: String,
+    pub attributes: CustomerReviewAttributes,
+    #[serde(default)]
+    pub relationships: CustomerReviewRelationships,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerReviewResponseAttributes {
+    #[serde(rename = "responseBody")]
+    pub response_body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerReviewsResponse {
+    pub data: Vec<CustomerReview>,
+    #[serde(default)]
+    pub included: Vec<AppStoreResource<CustomerReviewResponseAttributes>>,
+    #[serde(default)]
+    pub links: AppStoreLinks,
+}
+
+/// The filters of a sales and trends report, see the `salesReports` documentation
+/// for the valid combinations.
+#[derive(Debug)]
+pub struct SalesReportQuery {
+    pub frequency: String,
+    pub report_type: String,
+    pub report_sub_type: String,
+    pub report_date: Option<String>,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyticsReportRequestAttributes {
+    #[serde(rename = "accessType")]
+    pub access_type: String,
+    #[serde(rename = "stoppedDueToInactivity")]
+    #[serde(default)]
+    pub stopped_due_to_inactivity: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyticsReportAttributes {
+    pub name: String,
+    pub category: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyticsReportInstanceAttributes {
+    pub granularity: String,
+    #[serde(rename = "processingDate")]
+    pub processing_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyticsReportSegmentAttributes {
+    pub checksum: Option<String>,
+    #[serde(rename = "sizeInBytes")]
+    pub size_in_bytes: Option<u64>,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppStorePage<T> {
+    pub data: Vec<T>,
+    #[serde(default)]
+    pub links: AppStoreLinks,
+}
+
+#[derive(Debug)]
+pub struct FinanceReportQuery {
+    pub region_code: String,
+    pub report_date: String,
+    pub report_type: String,
+}
+
 pub type AppStoreVersionResponse = AppStoreMultiData<AppStoreVersionData>;
 pub type AppStoreVersionLocalizationResponse =
     AppStoreMultiData<AppStoreVersionLocalizationData<AppStoreVersionLocalizationAttributes>>;
 pub type AppStoreVersionLocalizationResponsePatch =
     AppStoreSingleData<AppStoreVersionLocalizationData<AppStoreVersionLocalizationAttributesPatch>>;
+pub type BuildResponse = AppStoreMultiData<AppStoreResource<BuildAttributes>>;
+pub type BetaBuildLocalizationResponse =
+    AppStoreMultiData<AppStoreResource<BetaBuildLocalizationAttributes>>;
+pub type AnalyticsReportRequest = AppStoreResource<AnalyticsReportRequestAttributes>;
+pub type AnalyticsReport = AppStoreResource<AnalyticsReportAttributes>;
+pub type AnalyticsReportInstance = AppStoreResource<AnalyticsReportInstanceAttributes>;
+pub type AnalyticsReportSegment = AppStoreResource<AnalyticsReportSegmentAttributes>;
+pub type AppResponse = AppStoreSingleData<AppStoreResource<AppAttributes>>;
+pub type BetaTesterResponse = AppStoreMultiData<AppStoreResource<BetaTesterAttributes>>;
+
+fn relationship_data(_type: &str, ids: &[String]) -> serde_json::Value {
+    return json!({
+        "data": ids
+            .iter()
+            .map(|id| json!({ "id": id, "type": _type }))
+            .collect::<Vec<_>>(),
+    });
+}
 
 impl AppStoreDataSource {
     pub fn new(token: String) -> Self {
         AppStoreDataSource { token }
     }
 
+    pub fn get_app(&self, app_id: &str) -> Result<AppResponse, String> {
+        return client()
+            .get(ep(format!("apps/{}", app_id).as_str()))
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<AppResponse>();
+    }
+
     pub fn get_app_version_localizations(
         &self,
         version_id: &str,
     ) -> Result<AppStoreVersionLocalizationResponse, String> {
-        let client = reqwest::blocking::Client::new()
+        return client()
             .get(ep(format!(
                 "appStoreVersions/{}/appStoreVersionLocalizations",
                 version_id
             )
             .as_str()))
-            .bearer_auth(self.token.clone());
-
-        let response = client.send().map_err(|e| e.to_string())?;
-        let response = response.text().map_err(|e| e.to_string())?;
-
-        let response: AppStoreVersionLocalizationResponse =
-            serde_json::from_str(&response).map_err(|e| e.to_string())?;
-
-        Ok(response)
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<AppStoreVersionLocalizationResponse>();
     }
 
     pub fn patch_whats_new(&self, localization_id: &str, whats_new: &str) -> Result<(), String> {
@@ -96,7 +279,7 @@ impl AppStoreDataSource {
 
         let body_str = serde_json::to_string(&body).map_err(|e| e.to_string())?;
 
-        let client = reqwest::blocking::Client::new()
+        let client = client()
             .patch(ep(format!(
                 "appStoreVersionLocalizations/{}",
                 localization_id
@@ -106,15 +289,12 @@ impl AppStoreDataSource {
             .header("Content-Type", "application/json")
             .body(body_str);
 
-        let response = client.send().map_err(|e| e.to_string())?;
+        let response = client.send_with_retry().map_err(|e| e.to_string())?;
 
         if response.status().is_success() {
             return Ok(());
         } else {
-            return Err(format!(
-                "Error: {}",
-                response.text().map_err(|e| e.to_string())?
-            ));
+            return Err(response_error(response));
         }
     }
 
@@ -123,14 +303,14 @@ impl AppStoreDataSource {
         app_id: &str,
         version: &str,
     ) -> Result<AppStoreVersionResponse, String> {
-        return reqwest::blocking::Client::new()
+        return client()
             .get(ep(format!(
                 "apps/{}/appStoreVersions?filter[versionString]={}",
                 app_id, version
             )
             .as_str()))
             .bearer_auth(self.token.clone())
-            .send()
+            .send_with_retry()
             .res::<AppStoreVersionResponse>();
     }
 
@@ -153,14 +333,477 @@ impl AppStoreDataSource {
             },
         });
 
-        return reqwest::blocking::Client::new()
+        return client()
             .post(ep("appStoreVersions"))
             .bearer_auth(token)
             .header(CONTENT_TYPE, "application/json")
             .body(serde_json::to_string(&request_body).map_err(|e| e.to_string())?)
-            .send()
+            .send_with_retry()
             .res::<Map<_, _>>()
             .map(|_| ())
             .map_err(|e| e.to_string());
     }
+
+    pub fn get_builds(&self, app_id: &str, build_number: &str) -> Result<BuildResponse, String> {
+        return client()
+            .get(ep(format!(
+                "builds?filter[app]={}&filter[version]={}",
+                app_id, build_number
+            )
+            .as_str()))
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<BuildResponse>();
+    }
+
+    pub fn get_beta_build_localizations(
+        &self,
+        build_id: &str,
+    ) -> Result<BetaBuildLocalizationResponse, String> {
+        return client()
+            .get(ep(
+                format!("builds/{}/betaBuildLocalizations", build_id).as_str()
+            ))
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<BetaBuildLocalizationResponse>();
+    }
+
+    pub fn create_beta_build_localization(
+        &self,
+        build_id: &str,
+        locale: &str,
+        whats_new: &str,
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "locale": locale,
+                    "whatsNew": whats_new,
+                },
+                "relationships": {
+                    "build": {
+                        "data": {
+                            "id": build_id,
+                            "type": "builds"
+                        }
+                    }
+                },
+                "type": "betaBuildLocalizations",
+            },
+        });
+
+        return client()
+            .post(ep("betaBuildLocalizations"))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<Map<_, _>>()
+            .map(|_| ());
+    }
+
+    pub fn patch_beta_whats_new(
+        &self,
+        localization_id: &str,
+        whats_new: &str,
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "whatsNew": whats_new,
+                },
+                "id": localization_id,
+                "type": "betaBuildLocalizations",
+            },
+        });
+
+        return client()
+            .patch(ep(
+                format!("betaBuildLocalizations/{}", localization_id).as_str()
+            ))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<Map<_, _>>()
+            .map(|_| ());
+    }
+
+    pub fn get_beta_groups(
+        &self,
+        app_id: &str,
+    ) -> Result<Vec<AppStoreResource<BetaGroupAttributes>>, String> {
+        return self.get_all(
+            format!("apps/{}/betaGroups", app_id).as_str(),
+            &[("limit", "200")],
+        );
+    }
+
+    pub fn add_build_to_beta_groups(
+        &self,
+        build_id: &str,
+        group_ids: &[String],
+    ) -> Result<(), String> {
+        return client()
+            .post(ep(
+                format!("builds/{}/relationships/betaGroups", build_id).as_str()
+            ))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(relationship_data("betaGroups", group_ids).to_string())
+            .send_idempotent()
+            .res_empty();
+    }
+
+    pub fn get_beta_testers(
+        &self,
+        app_id: &str,
+        email: &str,
+    ) -> Result<BetaTesterResponse, String> {
+        return client()
+            .get(ep("betaTesters"))
+            .query(&[("filter[apps]", app_id), ("filter[email]", email)])
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<BetaTesterResponse>();
+    }
+
+    pub fn create_beta_tester(
+        &self,
+        tester: &BetaTesterAttributes,
+        group_ids: &[String],
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": tester,
+                "relationships": {
+                    "betaGroups": relationship_data("betaGroups", group_ids),
+                },
+                "type": "betaTesters",
+            },
+        });
+
+        return client()
+            .post(ep("betaTesters"))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<Map<_, _>>()
+            .map(|_| ());
+    }
+
+    pub fn remove_beta_tester_from_group(
+        &self,
+        group_id: &str,
+        tester_id: &str,
+    ) -> Result<(), String> {
+        return client()
+            .delete(ep(format!(
+                "betaGroups/{}/relationships/betaTesters",
+                group_id
+            )
+            .as_str()))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(relationship_data("betaTesters", &[tester_id.to_string()]).to_string())
+            .send_with_retry()
+            .res_empty();
+    }
+
+    /// Removes the tester from the app only, deleting the `betaTesters` resource
+    /// would remove them from every app of the account.
+    pub fn remove_beta_tester_from_app(&self, tester_id: &str, app_id: &str) -> Result<(), String> {
+        return client()
+            .delete(ep(
+                format!("betaTesters/{}/relationships/apps", tester_id).as_str()
+            ))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(relationship_data("apps", &[app_id.to_string()]).to_string())
+            .send_with_retry()
+            .res_empty();
+    }
+
+    pub fn submit_for_beta_review(&self, build_id: &str) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "relationships": {
+                    "build": {
+                        "data": {
+                            "id": build_id,
+                            "type": "builds"
+                        }
+                    }
+                },
+                "type": "betaAppReviewSubmissions",
+            },
+        });
+
+        return client()
+            .post(ep("betaAppReviewSubmissions"))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<Map<_, _>>()
+            .map(|_| ());
+    }
+
+    pub fn get_customer_reviews(
+        &self,
+        app_id: &str,
+        ratings: &[u8],
+        territory: Option<&str>,
+    ) -> Result<CustomerReviewsResponse, String> {
+        let mut query = vec![
+            ("limit", "200".to_string()),
+            ("sort", "-createdDate".to_string()),
+            ("include", "response".to_string()),
+        ];
+
+        if !ratings.is_empty() {
+            let ratings: Vec<String> = ratings.iter().map(|r| r.to_string()).collect();
+            query.push(("filter[rating]", ratings.join(",")));
+        }
+
+        if let Some(territory) = territory {
+            query.push(("filter[territory]", territory.to_uppercase()));
+        }
+
+        return client()
+            .get(ep(format!("apps/{}/customerReviews", app_id).as_str()))
+            .query(&query)
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<CustomerReviewsResponse>();
+    }
+
+    /// Fetches the page pointed by the `links.next` of a previous response.
+    pub fn get_next_page<T>(&self, next: &str) -> Result<T, String>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        return client()
+            .get(next)
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<T>();
+    }
+
+    pub fn create_customer_review_response(
+        &self,
+        review_id: &str,
+        response_body: &str,
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "responseBody": response_body,
+                },
+                "relationships": {
+                    "review": {
+                        "data": {
+                            "id": review_id,
+                            "type": "customerReviews"
+                        }
+                    }
+                },
+                "type": "customerReviewResponses",
+            },
+        });
+
+        return client()
+            .post(ep("customerReviewResponses"))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<Map<_, _>>()
+            .map(|_| ());
+    }
+
+    pub fn get_sales_report(
+        &self,
+        vendor_number: &str,
+        query: &SalesReportQuery,
+    ) -> Result<Report, String> {
+        let mut filters = vec![
+            ("filter[frequency]", query.frequency.as_str()),
+            ("filter[reportType]", query.report_type.as_str()),
+            ("filter[reportSubType]", query.report_sub_type.as_str()),
+            ("filter[vendorNumber]", vendor_number),
+            ("filter[version]", query.version.as_str()),
+        ];
+
+        if let Some(report_date) = &query.report_date {
+            filters.push(("filter[reportDate]", report_date.as_str()));
+        }
+
+        return self.get_report("salesReports", &filters);
+    }
+
+    pub fn get_finance_report(
+        &self,
+        vendor_number: &str,
+        query: &FinanceReportQuery,
+    ) -> Result<Report, String> {
+        let filters = vec![
+            ("filter[regionCode]", query.region_code.as_str()),
+            ("filter[reportDate]", query.report_date.as_str()),
+            ("filter[reportType]", query.report_type.as_str()),
+            ("filter[vendorNumber]", vendor_number),
+        ];
+
+        return self.get_report("financeReports", &filters);
+    }
+
+    fn get_report(&self, endpoint: &str, filters: &[(&str, &str)]) -> Result<Report, String> {
+        let response = client()
+            .get(ep(endpoint))
+            .query(filters)
+            .bearer_auth(self.token.clone())
+            .header(ACCEPT, "application/a-gzip")
+            .send_with_retry()
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(response_error(response));
+        }
+
+        let bytes = response.bytes().map_err(|e| e.to_string())?;
+
+        return Report::from_gzip_tsv(&bytes);
+    }
+
+    /// Fetches every page of a collection by following the `links.next` of each page.
+    pub fn get_all<T>(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<Vec<T>, String>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut page = client()
+            .get(ep(endpoint))
+            .query(query)
+            .bearer_auth(self.token.clone())
+            .send_with_retry()
+            .res::<AppStorePage<T>>()?;
+        let mut items = page.data;
+
+        while let Some(next) = page.links.next {
+            page = self.get_next_page::<AppStorePage<T>>(&next)?;
+            items.append(&mut page.data);
+        }
+
+        return Ok(items);
+    }
+
+    pub fn get_analytics_report_requests(
+        &self,
+        app_id: &str,
+    ) -> Result<Vec<AnalyticsReportRequest>, String> {
+        return self.get_all(
+            format!("apps/{}/analyticsReportRequests", app_id).as_str(),
+            &[],
+        );
+    }
+
+    pub fn create_analytics_report_request(
+        &self,
+        app_id: &str,
+        access_type: &str,
+    ) -> Result<AnalyticsReportRequest, String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "accessType": access_type,
+                },
+                "relationships": {
+                    "app": {
+                        "data": {
+                            "id": app_id,
+                            "type": "apps"
+                        }
+                    }
+                },
+                "type": "analyticsReportRequests",
+            },
+        });
+
+        return client()
+            .post(ep("analyticsReportRequests"))
+            .bearer_auth(self.token.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .res::<AppStoreSingleData<AnalyticsReportRequest>>()
+            .map(|response| response.data);
+    }
+
+    pub fn get_analytics_reports(
+        &self,
+        request_id: &str,
+        category: Option<&str>,
+    ) -> Result<Vec<AnalyticsReport>, String> {
+        let query: Vec<(&str, &str)> = category
+            .map(|c| vec![("filter[category]", c)])
+            .unwrap_or_default();
+
+        return self.get_all(
+            format!("analyticsReportRequests/{}/reports", request_id).as_str(),
+            &query,
+        );
+    }
+
+    pub fn get_analytics_report_instances(
+        &self,
+        report_id: &str,
+        granularity: Option<&str>,
+        processing_date: Option<&str>,
+    ) -> Result<Vec<AnalyticsReportInstance>, String> {
+        let mut query: Vec<(&str, &str)> = vec![];
+
+        if let Some(granularity) = granularity {
+            query.push(("filter[granularity]", granularity));
+        }
+
+        if let Some(processing_date) = processing_date {
+            query.push(("filter[processingDate]", processing_date));
+        }
+
+        return self.get_all(
+            format!("analyticsReports/{}/instances", report_id).as_str(),
+            &query,
+        );
+    }
+
+    pub fn get_analytics_report_segments(
+        &self,
+        instance_id: &str,
+    ) -> Result<Vec<AnalyticsReportSegment>, String> {
+        return self.get_all(
+            format!("analyticsReportInstances/{}/segments", instance_id).as_str(),
+            &[],
+        );
+    }
+
+    /// Downloads a segment from its signed URL, which must not receive the token.
+    /// The file is written next to `path` and renamed once complete.
+    pub fn download_segment(url: &str, path: &Path) -> Result<u64, String> {
+        let mut response = client()
+            .get(url)
+            .send_with_retry()
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(response_error(response));
+        }
+
+        let partial_path = path.with_extension("part");
+        let mut file = fs::File::create(&partial_path).map_err(|e| e.to_string())?;
+        let size = io::copy(&mut response, &mut file).map_err(|e| e.to_string())?;
+        fs::rename(&partial_path, path).map_err(|e| e.to_string())?;
+
+        return Ok(size);
+    }
 }
@@ -0,0 +1,197 @@
+use super::{
+    app_store_datasource::{
+        ep, AppStoreVersionLocalizationResponse, AppStoreVersionResponse, CustomerReviewsResponse,
+    },
+    async_http::{async_client, AsyncRetryingRequest},
+    datasource::AsyncResponseMapper,
+};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Map};
+
+/// The App Store Connect calls of the `Store` operations, on the async client.
+/// See `AppStoreDataSource` for the blocking ones.
+pub struct AsyncAppStoreDataSource {
+    token: String,
+}
+
+impl AsyncAppStoreDataSource {
+    pub fn new(token: String) -> Self {
+        AsyncAppStoreDataSource { token }
+    }
+
+    pub fn token(&self) -> &str {
+        return &self.token;
+    }
+
+    pub async fn get_app_store_version(
+        &self,
+        app_id: &str,
+        version: &str,
+    ) -> Result<AppStoreVersionResponse, String> {
+        return async_client()
+            .get(ep(&format!(
+                "apps/{}/appStoreVersions?filter[versionString]={}",
+                app_id, version
+            )))
+            .bearer_auth(&self.token)
+            .send_with_retry()
+            .await
+            .res::<AppStoreVersionResponse>()
+            .await;
+    }
+
+    pub async fn get_app_version_localizations(
+        &self,
+        version_id: &str,
+    ) -> Result<AppStoreVersionLocalizationResponse, String> {
+        return async_client()
+            .get(ep(&format!(
+                "appStoreVersions/{}/appStoreVersionLocalizations",
+                version_id
+            )))
+            .bearer_auth(&self.token)
+            .send_with_retry()
+            .await
+            .res::<AppStoreVersionLocalizationResponse>()
+            .await;
+    }
+
+    pub async fn patch_whats_new(
+        &self,
+        localization_id: &str,
+        whats_new: &str,
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "type": "appStoreVersionLocalizations",
+                "id": localization_id,
+                "attributes": {
+                    "whatsNew": whats_new,
+                },
+            },
+        });
+
+        return async_client()
+            .patch(ep(&format!(
+                "appStoreVersionLocalizations/{}",
+                localization_id
+            )))
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .await
+            .res_empty()
+            .await;
+    }
+
+    pub async fn create_version(&self, app_id: &str, version: &str) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "platform": "IOS",
+                    "versionString": version,
+                },
+                "relationships": {
+                    "app": {
+                        "data": {
+                            "id": app_id,
+                            "type": "apps"
+                        }
+                    }
+                },
+                "type": "appStoreVersions",
+            },
+        });
+
+        return async_client()
+            .post(ep("appStoreVersions"))
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .await
+            .res::<Map<_, _>>()
+            .await
+            .map(|_| ());
+    }
+
+    pub async fn get_customer_reviews(
+        &self,
+        app_id: &str,
+        ratings: &[u8],
+        territory: Option<&str>,
+    ) -> Result<CustomerReviewsResponse, String> {
+        let mut query = vec![
+            ("limit", "200".to_string()),
+            ("sort", "-createdDate".to_string()),
+            ("include", "response".to_string()),
+        ];
+
+        if !ratings.is_empty() {
+            let ratings: Vec<String> = ratings.iter().map(|r| r.to_string()).collect();
+            query.push(("filter[rating]", ratings.join(",")));
+        }
+
+        if let Some(territory) = territory {
+            query.push(("filter[territory]", territory.to_uppercase()));
+        }
+
+        return async_client()
+            .get(ep(&format!("apps/{}/customerReviews", app_id)))
+            .query(&query)
+            .bearer_auth(&self.token)
+            .send_with_retry()
+            .await
+            .res::<CustomerReviewsResponse>()
+            .await;
+    }
+
+    /// Fetches the page pointed by the `links.next` of a previous response.
+    pub async fn get_next_page<T>(&self, next: &str) -> Result<T, String>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        return async_client()
+            .get(next)
+            .bearer_auth(&self.token)
+            .send_with_retry()
+            .await
+            .res::<T>()
+            .await;
+    }
+
+    pub async fn create_customer_review_response(
+        &self,
+        review_id: &str,
+        response_body: &str,
+    ) -> Result<(), String> {
+        let request_body = json!({
+            "data": {
+                "attributes": {
+                    "responseBody": response_body,
+                },
+                "relationships": {
+                    "review": {
+                        "data": {
+                            "id": review_id,
+                            "type": "customerReviews"
+                        }
+                    }
+                },
+                "type": "customerReviewResponses",
+            },
+        });
+
+        return async_client()
+            .post(ep("customerReviewResponses"))
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body.to_string())
+            .send_with_retry()
+            .await
+            .res::<Map<_, _>>()
+            .await
+            .map(|_| ());
+    }
+}
@@ -0,0 +1,167 @@
+use super::{
+    async_http::{async_client, AsyncRetryingRequest},
+    datasource::AsyncResponseMapper,
+    endpoints::endpoints,
+    google_play_datasource::{ReviewsListResponse, Track},
+};
+use reqwest::header::ACCEPT;
+use serde_json::json;
+
+/// The Google Play calls of the `Store` operations, on the async client. See
+/// `GooglePlayDataSource` for the blocking ones.
+pub struct AsyncGooglePlayDataSource {}
+
+impl AsyncGooglePlayDataSource {
+    pub async fn create_edit_session(token: &str, package_name: &str) -> Result<String, String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits",
+            endpoints().android_publisher,
+            package_name
+        );
+
+        return async_client()
+            .post(endpoint)
+            .bearer_auth(token)
+            .body("{}")
+            .send_idempotent()
+            .await
+            .res::<serde_json::Value>()
+            .await
+            .and_then(|json_body| {
+                json_body["id"]
+                    .as_str()
+                    .map(|id| id.to_string())
+                    .ok_or("No edit id in the response".to_string())
+            })
+            .map_err(|e| format!("Failed to create edit session: {}", e));
+    }
+
+    pub async fn get_track(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+        track: &str,
+    ) -> Result<Track, String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}/tracks/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track
+        );
+
+        return async_client()
+            .get(endpoint)
+            .bearer_auth(token)
+            .send_with_retry()
+            .await
+            .res::<Track>()
+            .await
+            .map_err(|e| format!("Failed to get tracks list: {}", e));
+    }
+
+    pub async fn update_track(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+        track: &Track,
+    ) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}/tracks/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track.track
+        );
+
+        return async_client()
+            .put(endpoint)
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .body(json!(track).to_string())
+            .send_with_retry()
+            .await
+            .res_empty()
+            .await
+            .map_err(|e| format!("Failed to patch track: {}", e));
+    }
+
+    pub async fn commit_edits(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+    ) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}:commit",
+            endpoints().android_publisher,
+            package_name,
+            edit_id
+        );
+
+        return async_client()
+            .post(endpoint)
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .body("{}")
+            .send_with_retry()
+            .await
+            .res_empty()
+            .await
+            .map_err(|e| format!("Failed to commit edits: {}", e));
+    }
+
+    /// Lists the reviews of the last week, the API does not return older ones.
+    pub async fn get_reviews(
+        token: &str,
+        package_name: &str,
+        page_token: Option<&str>,
+    ) -> Result<ReviewsListResponse, String> {
+        let endpoint = format!(
+            "{}/applications/{}/reviews",
+            endpoints().android_publisher,
+            package_name
+        );
+
+        let mut query = vec![("maxResults", "100")];
+
+        if let Some(page_token) = page_token {
+            query.push(("token", page_token));
+        }
+
+        return async_client()
+            .get(endpoint)
+            .query(&query)
+            .bearer_auth(token)
+            .send_with_retry()
+            .await
+            .res::<ReviewsListResponse>()
+            .await
+            .map_err(|e| format!("Failed to get reviews: {}", e));
+    }
+
+    pub async fn reply_to_review(
+        token: &str,
+        package_name: &str,
+        review_id: &str,
+        reply_text: &str,
+    ) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/reviews/{}:reply",
+            endpoints().android_publisher,
+            package_name,
+            review_id
+        );
+
+        return async_client()
+            .post(endpoint)
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .body(json!({ "replyText": reply_text }).to_string())
+            .send_idempotent()
+            .await
+            .res::<serde_json::Value>()
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to reply to review: {}", e));
+    }
+}
@@ -0,0 +1,106 @@
+use super::{
+    capture,
+    http::{
+        certificates, client_settings, is_idempotent, proxy, record_token, retry_policy,
+        token_hash, USER_AGENT,
+    },
+    logging,
+};
+use reqwest::{Client, RequestBuilder, Response};
+use std::{sync::OnceLock, time::Instant};
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// The async client shared by every async API call, with the settings given
+/// to `http::init_client`.
+pub fn async_client() -> &'static Client {
+    return CLIENT.get_or_init(|| build_client().expect("Could not create the HTTP client"));
+}
+
+fn build_client() -> Result<Client, String> {
+    let settings = client_settings();
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(settings.connect_timeout)
+        .timeout(settings.timeout);
+
+    if let Some(proxy) = proxy(settings)? {
+        builder = builder.proxy(proxy);
+    }
+
+    for certificate in certificates(settings)? {
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    return builder.build().map_err(|e| e.to_string());
+}
+
+/// The async counterpart of `http::RetryingRequest`, with the same retry policy.
+// The futures are `Send` as long as the request is, which the callers rely on.
+#[allow(async_fn_in_trait)]
+pub trait AsyncRetryingRequest {
+    async fn send_with_retry(self) -> Result<Response, reqwest::Error>;
+
+    async fn send_idempotent(self) -> Result<Response, reqwest::Error>;
+}
+
+impl AsyncRetryingRequest for RequestBuilder {
+    async fn send_with_retry(self) -> Result<Response, reqwest::Error> {
+        let is_idempotent = self
+            .try_clone()
+            .and_then(|builder| builder.build().ok())
+            .is_some_and(|request| is_idempotent(request.method()));
+
+        return send(self, is_idempotent).await;
+    }
+
+    async fn send_idempotent(self) -> Result<Response, reqwest::Error> {
+        return send(self, true).await;
+    }
+}
+
+async fn send(builder: RequestBuilder, is_idempotent: bool) -> Result<Response, reqwest::Error> {
+    let policy = retry_policy();
+    let token = builder
+        .try_clone()
+        .and_then(|builder| builder.build().ok())
+        .and_then(|request| token_hash(request.headers()));
+    let mut attempt = 0;
+
+    loop {
+        // Streamed bodies can't be cloned, these requests are sent once.
+        let Some(request) = builder.try_clone().filter(|_| attempt < policy.max_retries) else {
+            let result = execute(builder).await;
+            record_token(token, result.as_ref().map(|r| r.status()));
+            return result;
+        };
+
+        let result = execute(request).await;
+        record_token(token, result.as_ref().map(|r| r.status()));
+        let outcome = result.as_ref().map(|r| (r.status(), r.headers()));
+        let Some(delay) = policy.retry_delay(attempt, is_idempotent, outcome) else {
+            return result;
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Sends the request once, logging it.
+async fn execute(builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+    if !logging::enabled(1) {
+        return capture::execute_async(builder).await;
+    }
+
+    let request = builder.try_clone().and_then(|b| b.build().ok());
+    let (method, url) =
+        logging::request(request.as_ref().map(|r| (r.method(), r.url(), r.headers())));
+
+    let start = Instant::now();
+    let result = capture::execute_async(builder).await;
+    let outcome = result.as_ref().map(|r| (r.status(), r.headers()));
+    logging::response(&method, &url, outcome, start.elapsed());
+
+    return result;
+}
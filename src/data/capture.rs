@@ -0,0 +1,480 @@
+use super::logging;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{blocking::Response, header::HeaderMap, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+const REDACTED: &str = "REDACTED";
+
+/// The headers and body fields holding credentials, never written to disk.
+const SECRET_HEADERS: [&str; 4] = ["authorization", "cookie", "set-cookie", "x-goog-api-key"];
+const SECRET_FIELDS: [&str; 9] = [
+    "access_token",
+    "accessToken",
+    "assertion",
+    "client_secret",
+    "id_token",
+    "private_key",
+    "refresh_token",
+    "subject_token",
+    "token",
+];
+/// The query parameters signing a pre-signed URL, compared case-insensitively.
+const SIGNATURE_PARAMS: [&str; 7] = [
+    "x-amz-signature",
+    "x-amz-credential",
+    "x-amz-security-token",
+    "x-goog-signature",
+    "x-goog-credential",
+    "signature",
+    "sig",
+];
+
+static CAPTURE: OnceLock<Capture> = OnceLock::new();
+
+/// Records every exchange with the APIs to a directory, or serves them back
+/// from it without touching the network.
+pub enum Capture {
+    Record {
+        dir: PathBuf,
+        count: Mutex<usize>,
+    },
+    Replay {
+        exchanges: Mutex<Vec<Option<Exchange>>>,
+    },
+}
+
+/// A request and its response, as saved to `{dir}/{sequence}.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The body when it is text, the base64 encoded bytes otherwise.
+    pub body: Option<String>,
+    pub body_base64: Option<String>,
+}
+
+impl Capture {
+    /// Records to `dir`, after the exchanges already recorded there.
+    pub fn record(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        return Ok(Capture::Record {
+            dir: dir.to_path_buf(),
+            count: Mutex::new(recordings(dir)?.len()),
+        });
+    }
+
+    /// Loads the exchanges recorded in `dir`, in the order they were recorded.
+    pub fn replay(dir: &Path) -> Result<Self, String> {
+        let exchanges = recordings(dir)?
+            .iter()
+            .map(|path| {
+                let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+                return serde_json::from_str::<Exchange>(&content)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid recording {}: {}", path.display(), e));
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        return Ok(Capture::Replay {
+            exchanges: Mutex::new(exchanges),
+        });
+    }
+}
+
+/// The recordings in `dir`, in order.
+fn recordings(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "json"))
+        .collect();
+    paths.sort();
+
+    return Ok(paths);
+}
+
+/// Sets the capture mode of every API call, once at startup.
+pub fn set_capture(capture: Capture) {
+    let _ = CAPTURE.set(capture);
+}
+
+/// Whether the API calls are served from recordings, their tokens being fake.
+pub fn is_replaying() -> bool {
+    return matches!(CAPTURE.get(), Some(Capture::Replay { .. }));
+}
+
+/// Sends the request, recording or replaying it when a capture mode is set.
+/// Replayed requests get the first unused response recorded for the same
+/// method, URL and body.
+pub fn execute(request: reqwest::blocking::RequestBuilder) -> Result<Response, reqwest::Error> {
+    let Some(capture) = CAPTURE.get() else {
+        return request.send();
+    };
+
+    if let Capture::Replay { .. } = capture {
+        let request = request.build()?;
+        let recorded = record_request(
+            request.method(),
+            request.url(),
+            request.headers(),
+            request.body().and_then(|b| b.as_bytes()),
+        );
+
+        return Ok(Response::from(capture.replayed_response(&recorded)));
+    }
+
+    let recorded = request.try_clone().and_then(|r| r.build().ok()).map(|r| {
+        record_request(
+            r.method(),
+            r.url(),
+            r.headers(),
+            r.body().and_then(|b| b.as_bytes()),
+        )
+    });
+
+    let response = request.send()?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.bytes()?.to_vec();
+
+    return Ok(Response::from(
+        capture.recorded_response(recorded, status, headers, bytes),
+    ));
+}
+
+/// Sends the request of the async client, see `execute`.
+#[cfg(feature = "async")]
+pub async fn execute_async(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let Some(capture) = CAPTURE.get() else {
+        return request.send().await;
+    };
+
+    if let Capture::Replay { .. } = capture {
+        let request = request.build()?;
+        let recorded = record_request(
+            request.method(),
+            request.url(),
+            request.headers(),
+            request.body().and_then(|b| b.as_bytes()),
+        );
+
+        return Ok(reqwest::Response::from(
+            capture.replayed_response(&recorded),
+        ));
+    }
+
+    let recorded = request.try_clone().and_then(|r| r.build().ok()).map(|r| {
+        record_request(
+            r.method(),
+            r.url(),
+            r.headers(),
+            r.body().and_then(|b| b.as_bytes()),
+        )
+    });
+
+    let response = request.send().await?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.bytes().await?.to_vec();
+
+    return Ok(reqwest::Response::from(
+        capture.recorded_response(recorded, status, headers, bytes),
+    ));
+}
+
+impl Capture {
+    /// The first unused response recorded for the request, or a 599 saying
+    /// there is none left.
+    fn replayed_response(&self, request: &RecordedRequest) -> http::Response<Vec<u8>> {
+        let exchange = match self {
+            Capture::Replay { exchanges } => exchanges
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|e| {
+                    e.as_ref().is_some_and(|e| {
+                        e.request.method == request.method
+                            && e.request.url == request.url
+                            && e.request.body == request.body
+                    })
+                })
+                .and_then(|e| e.take()),
+            Capture::Record { .. } => None,
+        };
+
+        match exchange {
+            Some(exchange) => return to_response(exchange.response),
+            None => {
+                return to_response(RecordedResponse {
+                    status: 599,
+                    headers: vec![],
+                    body: Some(format!(
+                        "No recorded response left for {} {}",
+                        request.method, request.url
+                    )),
+                    body_base64: None,
+                })
+            }
+        }
+    }
+
+    /// Saves the exchange, returning the response to hand to the caller, as
+    /// its body was consumed by the recording.
+    fn recorded_response(
+        &self,
+        request: Option<RecordedRequest>,
+        status: StatusCode,
+        headers: HeaderMap,
+        bytes: Vec<u8>,
+    ) -> http::Response<Vec<u8>> {
+        if let (Capture::Record { dir, count }, Some(request)) = (self, request) {
+            let response = RecordedResponse {
+                status: status.as_u16(),
+                headers: redact_headers(&headers),
+                body: std::str::from_utf8(&bytes).ok().map(redact_body),
+                body_base64: match std::str::from_utf8(&bytes) {
+                    Ok(_) => None,
+                    Err(_) => Some(STANDARD.encode(&bytes)),
+                },
+            };
+
+            let mut count = count.lock().unwrap();
+            *count += 1;
+
+            let path = dir.join(format!("{:04}.json", count));
+
+            if let Err(e) = serde_json::to_string_pretty(&Exchange { request, response })
+                .map_err(|e| e.to_string())
+                .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()))
+            {
+                logging::log(
+                    0,
+                    "record",
+                    &format!("Failed to record {}: {}", path.display(), e),
+                    serde_json::json!({ "path": path.display().to_string(), "error": e }),
+                );
+            }
+        }
+
+        let mut response = http::Response::new(bytes);
+        *response.status_mut() = status;
+        *response.headers_mut() = headers;
+
+        return response;
+    }
+}
+
+fn record_request(
+    method: &Method,
+    url: &Url,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> RecordedRequest {
+    return RecordedRequest {
+        method: method.to_string(),
+        url: redact_url(url),
+        headers: redact_headers(headers),
+        body: body
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .map(redact_body),
+    };
+}
+
+/// The headers, with the credentials redacted.
+pub fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    return headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match SECRET_HEADERS.contains(&name.as_str()) {
+                true => REDACTED.to_string(),
+                false => value.to_str().unwrap_or_default().to_string(),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+}
+
+/// The URL, with the credentials and signatures of its query redacted.
+pub fn redact_url(url: &Url) -> String {
+    if !url.query_pairs().any(|(key, _)| is_secret_param(&key)) {
+        return url.to_string();
+    }
+
+    let mut redacted = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| match is_secret_param(&key) {
+            true => (key.to_string(), REDACTED.to_string()),
+            false => (key.to_string(), value.to_string()),
+        })
+        .collect();
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
+
+    return redacted.to_string();
+}
+
+fn is_secret_param(key: &str) -> bool {
+    return SECRET_FIELDS.contains(&key)
+        || key == "key"
+        || SIGNATURE_PARAMS
+            .iter()
+            .any(|param| param.eq_ignore_ascii_case(key));
+}
+
+/// Redacts the secret fields of a JSON or form encoded body.
+fn redact_body(body: &str) -> String {
+    if let Ok(mut json) = serde_json::from_str::<Value>(body) {
+        redact_json(&mut json);
+        return json.to_string();
+    }
+
+    if body.contains('=') && !body.contains(char::is_whitespace) {
+        return body
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if SECRET_FIELDS.contains(&key) => format!("{}={}", key, REDACTED),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+    }
+
+    return body.to_string();
+}
+
+fn redact_json(json: &mut Value) {
+    match json {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) && value.is_string() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_json),
+        // The download links of the reports are pre-signed.
+        Value::String(value) if value.starts_with("https://") || value.starts_with("http://") => {
+            if let Ok(url) = Url::parse(value) {
+                *value = redact_url(&url);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn to_response(recorded: RecordedResponse) -> http::Response<Vec<u8>> {
+    let body = match (recorded.body, recorded.body_base64) {
+        (_, Some(base64)) => STANDARD.decode(base64).unwrap_or_default(),
+        (Some(body), None) => body.into_bytes(),
+        (None, None) => vec![],
+    };
+
+    let mut builder = http::Response::builder().status(recorded.status);
+
+    for (name, value) in &recorded.headers {
+        builder = builder.header(name, value);
+    }
+
+    return builder
+        .body(body)
+        .unwrap_or_else(|_| http::Response::new(vec![]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_url_redacts_signatures_and_credentials() {
+        let url = Url::parse(
+            "https://bucket.s3.amazonaws.com/report.gz?X-Amz-Algorithm=AWS4-HMAC-SHA256\
+             &X-Amz-Credential=AKIA%2F20261018&X-Amz-Date=20261018T000000Z\
+             &X-Amz-Expires=300&X-Amz-Security-Token=session&X-Amz-Signature=abc123",
+        )
+        .unwrap();
+
+        assert_eq!(
+            redact_url(&url),
+            "https://bucket.s3.amazonaws.com/report.gz?X-Amz-Algorithm=AWS4-HMAC-SHA256\
+             &X-Amz-Credential=REDACTED&X-Amz-Date=20261018T000000Z\
+             &X-Amz-Expires=300&X-Amz-Security-Token=REDACTED&X-Amz-Signature=REDACTED"
+        );
+    }
+
+    #[test]
+    fn redact_url_keeps_urls_without_secrets() {
+        let url = "https://api.appstoreconnect.apple.com/v1/apps/1/betaGroups?limit=200";
+
+        assert_eq!(redact_url(&Url::parse(url).unwrap()), url);
+    }
+
+    #[test]
+    fn redact_url_redacts_keys_and_lowercase_signatures() {
+        let url =
+            Url::parse("https://example.com/a?key=secret&sig=abc&signature=def&page=2").unwrap();
+
+        assert_eq!(
+            redact_url(&url),
+            "https://example.com/a?key=REDACTED&sig=REDACTED&signature=REDACTED&page=2"
+        );
+    }
+
+    #[test]
+    fn redact_body_redacts_secret_fields_and_signed_urls() {
+        let body = serde_json::json!({
+            "access_token": "ya29.secret",
+            "data": [{
+                "attributes": {
+                    "url": "https://bucket.s3.amazonaws.com/r.gz?X-Amz-Signature=abc&X-Amz-Expires=300",
+                    "name": "https://not a url?sig=abc",
+                },
+            }],
+        });
+
+        let redacted: Value = serde_json::from_str(&redact_body(&body.to_string())).unwrap();
+
+        assert_eq!(redacted["access_token"], REDACTED);
+        assert_eq!(
+            redacted["data"][0]["attributes"]["url"],
+            "https://bucket.s3.amazonaws.com/r.gz?X-Amz-Signature=REDACTED&X-Amz-Expires=300"
+        );
+        assert_eq!(
+            redacted["data"][0]["attributes"]["name"],
+            "https://not a url?sig=abc"
+        );
+    }
+
+    #[test]
+    fn redact_body_redacts_form_fields() {
+        assert_eq!(
+            redact_body("grant_type=refresh_token&refresh_token=1%2F2&client_id=id"),
+            "grant_type=refresh_token&refresh_token=REDACTED&client_id=id"
+        );
+    }
+}
@@ -1,3 +1,25 @@
+use super::logging::request_ids;
+
+/// Formats the error of an unsuccessful response from its body, with the
+/// request ids to quote when opening a ticket.
+pub fn response_error(response: reqwest::blocking::Response) -> String {
+    let status = response.status();
+    let request_ids = request_ids(response.headers())
+        .iter()
+        .map(|(name, value)| format!(" ({}: {})", name, value))
+        .collect::<String>();
+    let body = match response.text() {
+        Ok(body) => body,
+        Err(e) => return e.to_string(),
+    };
+
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return format!("UNAUTHORIZED: {}{}", body, request_ids);
+    } else {
+        return format!("ERROR: {}{}", body, request_ids);
+    }
+}
+
 pub fn res<T>(response: Result<reqwest::blocking::Response, reqwest::Error>) -> Result<T, String>
 where
     T: serde::de::DeserializeOwned,
@@ -11,10 +33,19 @@ where
         let result = serde_json::from_value::<T>(json_body).map_err(|e| e.to_string())?;
         return Ok(result);
     } else {
-        return Err(format!(
-            "ERROR: {}",
-            response.text().map_err(|e| e.to_string())?
-        ));
+        return Err(response_error(response));
+    }
+}
+
+pub fn res_empty(
+    response: Result<reqwest::blocking::Response, reqwest::Error>,
+) -> Result<(), String> {
+    let response = response.map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        return Ok(());
+    } else {
+        return Err(response_error(response));
     }
 }
 
@@ -22,6 +53,9 @@ pub trait ResponseMapper {
     fn res<T>(self) -> Result<T, String>
     where
         T: serde::de::DeserializeOwned;
+
+    /// Checks the status of a response whose body is irrelevant or empty, such as a 204.
+    fn res_empty(self) -> Result<(), String>;
 }
 
 impl ResponseMapper for Result<reqwest::blocking::Response, reqwest::Error> {
@@ -31,4 +65,67 @@ impl ResponseMapper for Result<reqwest::blocking::Response, reqwest::Error> {
     {
         return res::<T>(self);
     }
+
+    fn res_empty(self) -> Result<(), String> {
+        return res_empty(self);
+    }
+}
+
+/// The async counterpart of `ResponseMapper`.
+#[cfg(feature = "async")]
+#[allow(async_fn_in_trait)]
+pub trait AsyncResponseMapper {
+    async fn res<T>(self) -> Result<T, String>
+    where
+        T: serde::de::DeserializeOwned;
+
+    async fn res_empty(self) -> Result<(), String>;
+}
+
+#[cfg(feature = "async")]
+impl AsyncResponseMapper for Result<reqwest::Response, reqwest::Error> {
+    async fn res<T>(self) -> Result<T, String>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let response = self.map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(async_response_error(response).await);
+        }
+
+        let body = response.text().await.map_err(|e| e.to_string())?;
+
+        return serde_json::from_str::<T>(&body).map_err(|e| e.to_string());
+    }
+
+    async fn res_empty(self) -> Result<(), String> {
+        let response = self.map_err(|e| e.to_string())?;
+
+        if response.status().is_success() {
+            return Ok(());
+        } else {
+            return Err(async_response_error(response).await);
+        }
+    }
+}
+
+/// The async counterpart of `response_error`.
+#[cfg(feature = "async")]
+async fn async_response_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    let request_ids = request_ids(response.headers())
+        .iter()
+        .map(|(name, value)| format!(" ({}: {})", name, value))
+        .collect::<String>();
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => return e.to_string(),
+    };
+
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return format!("UNAUTHORIZED: {}{}", body, request_ids);
+    } else {
+        return format!("ERROR: {}{}", body, request_ids);
+    }
 }
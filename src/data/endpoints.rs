@@ -0,0 +1,33 @@
+use std::sync::OnceLock;
+
+static ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+
+/// The base URLs of the APIs, overridable to go through a gateway or to reach a
+/// mock server.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub app_store_connect: String,
+    pub android_publisher: String,
+    pub oauth_token: String,
+    pub play_reporting: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        return Self {
+            app_store_connect: "https://api.appstoreconnect.apple.com/v1".to_string(),
+            android_publisher: "https://www.googleapis.com/androidpublisher/v3".to_string(),
+            oauth_token: "https://oauth2.googleapis.com/token".to_string(),
+            play_reporting: "https://playdeveloperreporting.googleapis.com/v1beta1".to_string(),
+        };
+    }
+}
+
+/// Sets the base URLs of every API call, once at startup.
+pub fn set_endpoints(endpoints: Endpoints) {
+    let _ = ENDPOINTS.set(endpoints);
+}
+
+pub fn endpoints() -> &'static Endpoints {
+    return ENDPOINTS.get_or_init(Endpoints::default);
+}
@@ -1,10 +1,15 @@
-use reqwest::header::ACCEPT;
+use chrono::{DateTime, Utc};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::data::datasource::ResponseMapper;
+use crate::data::{
+    datasource::ResponseMapper,
+    endpoints::endpoints,
+    http::{client, RetryingRequest},
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ReleaseNote {
     pub language: String,
     pub text: String,
@@ -28,7 +33,7 @@ pub enum ReleaseStatus {
     Completed,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Release {
     #[serde(rename = "versionCodes")]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -41,6 +46,54 @@ pub struct Release {
     pub status: ReleaseStatus,
 
     pub name: String,
+
+    #[serde(rename = "userFraction")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_fraction: Option<f64>,
+
+    #[serde(rename = "countryTargeting")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_targeting: Option<CountryTargeting>,
+
+    #[serde(rename = "inAppUpdatePriority")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_app_update_priority: Option<u8>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CountryTargeting {
+    pub countries: Vec<String>,
+
+    #[serde(rename = "includeRestOfWorld")]
+    #[serde(default)]
+    pub include_rest_of_world: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Testers {
+    #[serde(rename = "googleGroups")]
+    #[serde(default)]
+    pub google_groups: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrackCountry {
+    #[serde(rename = "countryCode")]
+    pub country_code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrackCountryAvailability {
+    #[serde(default)]
+    pub countries: Vec<TrackCountry>,
+
+    #[serde(rename = "restOfWorld")]
+    #[serde(default)]
+    pub rest_of_world: bool,
+
+    #[serde(rename = "syncWithProduction")]
+    #[serde(default)]
+    pub sync_with_production: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -49,37 +102,198 @@ pub struct Track {
     pub releases: Vec<Release>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Timestamp {
+    pub seconds: String,
+
+    #[serde(default)]
+    pub nanos: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserComment {
+    pub text: String,
+
+    #[serde(rename = "lastModified")]
+    pub last_modified: Timestamp,
+
+    #[serde(rename = "starRating")]
+    pub star_rating: u8,
+
+    #[serde(rename = "reviewerLanguage")]
+    pub reviewer_language: Option<String>,
+
+    #[serde(rename = "appVersionName")]
+    pub app_version_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeveloperComment {
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Comment {
+    #[serde(rename = "userComment")]
+    pub user_comment: Option<UserComment>,
+
+    #[serde(rename = "developerComment")]
+    pub developer_comment: Option<DeveloperComment>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PlayReview {
+    #[serde(rename = "reviewId")]
+    pub review_id: String,
+
+    #[serde(rename = "authorName")]
+    pub author_name: Option<String>,
+
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenPagination {
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReviewsListResponse {
+    #[serde(default)]
+    pub reviews: Vec<PlayReview>,
+
+    #[serde(rename = "tokenPagination")]
+    pub token_pagination: Option<TokenPagination>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthToken {
+    pub access_token: String,
+
+    /// The lifetime of the token in seconds.
+    pub expires_in: u64,
+}
+
+/// The access token of an impersonated service account.
+#[derive(Debug, Deserialize)]
+pub struct ImpersonatedToken {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+
+    #[serde(rename = "expireTime")]
+    pub expire_time: DateTime<Utc>,
+}
+
+/// The Google Play Android Developer API, and the Google token endpoints.
 pub struct GooglePlayDataSource {}
 
 impl GooglePlayDataSource {
-    pub fn get_signed_token(token: &str) -> Result<String, String> {
+    pub fn get_signed_token(token: &str) -> Result<OAuthToken, String> {
         let body_json = json!({
             "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
             "assertion": token,
         });
 
-        return reqwest::blocking::Client::new()
-            .post("https://oauth2.googleapis.com/token")
+        return client()
+            .post(&endpoints().oauth_token)
             .body(body_json.to_string())
-            .send()
-            .res::<serde_json::Value>()
-            .map(|json_body| json_body["access_token"].as_str().unwrap().to_string())
+            .send_idempotent()
+            .res::<OAuthToken>()
             .map_err(|e| format!("Failed to get signed token: {}", e));
     }
 
+    /// Exchanges the refresh token of an `authorized_user` credential for an access token.
+    pub fn refresh_access_token(
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<OAuthToken, String> {
+        return client()
+            .post(&endpoints().oauth_token)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("refresh_token", refresh_token),
+            ])
+            .send_idempotent()
+            .res::<OAuthToken>()
+            .map_err(|e| format!("Failed to refresh access token: {}", e));
+    }
+
+    /// Exchanges a third party token for a federated access token through the
+    /// Security Token Service, used by the `external_account` credentials.
+    pub fn exchange_sts_token(
+        token_url: &str,
+        audience: &str,
+        scope: &str,
+        subject_token: &str,
+        subject_token_type: &str,
+    ) -> Result<OAuthToken, String> {
+        return client()
+            .post(token_url)
+            .form(&[
+                (
+                    "grant_type",
+                    "urn:ietf:params:oauth:grant-type:token-exchange",
+                ),
+                (
+                    "requested_token_type",
+                    "urn:ietf:params:oauth:token-type:access_token",
+                ),
+                ("audience", audience),
+                ("scope", scope),
+                ("subject_token", subject_token),
+                ("subject_token_type", subject_token_type),
+            ])
+            .send_idempotent()
+            .res::<OAuthToken>()
+            .map_err(|e| format!("Failed to exchange STS token: {}", e));
+    }
+
+    /// Generates an access token of the service account behind `url` with a
+    /// federated token allowed to impersonate it.
+    pub fn generate_access_token(
+        token: &str,
+        url: &str,
+        scopes: &[&str],
+    ) -> Result<ImpersonatedToken, String> {
+        let body_json = json!({
+            "scope": scopes,
+            "lifetime": "3600s",
+        });
+
+        return client()
+            .post(url)
+            .bearer_auth(token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body_json.to_string())
+            .send_idempotent()
+            .res::<ImpersonatedToken>()
+            .map_err(|e| format!("Failed to impersonate service account: {}", e));
+    }
+
     pub fn create_edit_session(token: &str, package_name: &str) -> Result<String, String> {
         let endpoint = format!(
-            "https://www.googleapis.com/androidpublisher/v3/applications/{}/edits",
+            "{}/applications/{}/edits",
+            endpoints().android_publisher,
             package_name
         );
 
-        return reqwest::blocking::Client::new()
+        return client()
             .post(endpoint)
             .bearer_auth(token)
             .body("{}")
-            .send()
+            .send_idempotent()
             .res::<serde_json::Value>()
-            .map(|json_body| json_body["id"].as_str().unwrap().to_string())
+            .and_then(|json_body| {
+                json_body["id"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or("The edit has no id".to_string())
+            })
             .map_err(|e| format!("Failed to create edit session: {}", e));
     }
 
@@ -87,21 +301,24 @@ impl GooglePlayDataSource {
         token: &str,
         package_name: &str,
         edit_id: &str,
-        track: Track,
+        track: &Track,
     ) -> Result<(), String> {
         let endpoint = format!(
-            "https://www.googleapis.com/androidpublisher/v3/applications/{}/edits/{}/tracks/{}",
-            package_name, edit_id, track.track
+            "{}/applications/{}/edits/{}/tracks/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track.track
         );
         let req_body = json!(track).to_string();
 
-        return reqwest::blocking::Client::new()
+        return client()
             .put(endpoint)
             .bearer_auth(token)
             .header(ACCEPT, "application/json")
             .body(req_body)
-            .send()
-            .map(|_| ())
+            .send_with_retry()
+            .res_empty()
             .map_err(|e| format!("Failed to patch track: {}", e));
     }
 
@@ -112,31 +329,174 @@ impl GooglePlayDataSource {
         track: &str,
     ) -> Result<Track, String> {
         let endpoint = format!(
-            "https://www.googleapis.com/androidpublisher/v3/applications/{}/edits/{}/tracks/{}",
-            package_name, edit_id, track
+            "{}/applications/{}/edits/{}/tracks/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track
         );
 
-        return reqwest::blocking::Client::new()
+        return client()
             .get(endpoint)
             .bearer_auth(token)
-            .send()
+            .send_with_retry()
             .res::<Track>()
             .map_err(|e| format!("Failed to get tracks list: {}", e));
     }
 
+    /// Discards an edit, for the edits only used to read the current state.
+    pub fn delete_edit(token: &str, package_name: &str, edit_id: &str) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id
+        );
+
+        return client()
+            .delete(endpoint)
+            .bearer_auth(token)
+            .send_with_retry()
+            .res_empty()
+            .map_err(|e| format!("Failed to delete edit: {}", e));
+    }
+
     pub fn commit_edits(token: &str, package_name: &str, edit_id: &str) -> Result<(), String> {
         let endpoint = format!(
-            "https://www.googleapis.com/androidpublisher/v3/applications/{}/edits/{}:commit",
-            package_name, edit_id
+            "{}/applications/{}/edits/{}:commit",
+            endpoints().android_publisher,
+            package_name,
+            edit_id
         );
 
-        return reqwest::blocking::Client::new()
+        return client()
             .post(endpoint)
             .bearer_auth(token)
             .header(ACCEPT, "application/json")
             .body("{}")
-            .send()
-            .map(|_| ())
+            .send_with_retry()
+            .res_empty()
             .map_err(|e| format!("Failed to commit edits: {}", e));
     }
+
+    pub fn get_testers(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+        track: &str,
+    ) -> Result<Testers, String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}/testers/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track
+        );
+
+        return client()
+            .get(endpoint)
+            .bearer_auth(token)
+            .send_with_retry()
+            .res::<Testers>()
+            .map_err(|e| format!("Failed to get testers: {}", e));
+    }
+
+    pub fn update_testers(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+        track: &str,
+        testers: &Testers,
+    ) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}/testers/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track
+        );
+
+        return client()
+            .put(endpoint)
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .body(json!(testers).to_string())
+            .send_with_retry()
+            .res::<Testers>()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to update testers: {}", e));
+    }
+
+    pub fn get_country_availability(
+        token: &str,
+        package_name: &str,
+        edit_id: &str,
+        track: &str,
+    ) -> Result<TrackCountryAvailability, String> {
+        let endpoint = format!(
+            "{}/applications/{}/edits/{}/countryAvailability/{}",
+            endpoints().android_publisher,
+            package_name,
+            edit_id,
+            track
+        );
+
+        return client()
+            .get(endpoint)
+            .bearer_auth(token)
+            .send_with_retry()
+            .res::<TrackCountryAvailability>()
+            .map_err(|e| format!("Failed to get country availability: {}", e));
+    }
+
+    /// Lists the reviews of the last week, the API does not return older ones.
+    pub fn get_reviews(
+        token: &str,
+        package_name: &str,
+        page_token: Option<&str>,
+    ) -> Result<ReviewsListResponse, String> {
+        let endpoint = format!(
+            "{}/applications/{}/reviews",
+            endpoints().android_publisher,
+            package_name
+        );
+
+        let mut query = vec![("maxResults", "100")];
+
+        if let Some(page_token) = page_token {
+            query.push(("token", page_token));
+        }
+
+        return client()
+            .get(endpoint)
+            .query(&query)
+            .bearer_auth(token)
+            .send_with_retry()
+            .res::<ReviewsListResponse>()
+            .map_err(|e| format!("Failed to get reviews: {}", e));
+    }
+
+    pub fn reply_to_review(
+        token: &str,
+        package_name: &str,
+        review_id: &str,
+        reply_text: &str,
+    ) -> Result<(), String> {
+        let endpoint = format!(
+            "{}/applications/{}/reviews/{}:reply",
+            endpoints().android_publisher,
+            package_name,
+            review_id
+        );
+
+        return client()
+            .post(endpoint)
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .body(json!({ "replyText": reply_text }).to_string())
+            .send_idempotent()
+            .res::<serde_json::Value>()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to reply to review: {}", e));
+    }
 }
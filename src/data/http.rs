@@ -0,0 +1,484 @@
+use super::{capture, logging};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::{HeaderMap, AUTHORIZATION, RETRY_AFTER},
+    Certificate, Method, Proxy, StatusCode,
+};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fs,
+    hash::{BuildHasher, DefaultHasher, Hash, Hasher},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The statuses worth trying again, the server may answer differently later.
+const RETRYABLE_STATUSES: [StatusCode; 5] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+pub(super) const USER_AGENT: &str = concat!("freitool/", env!("CARGO_PKG_VERSION"));
+
+static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+static SETTINGS: OnceLock<ClientSettings> = OnceLock::new();
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// The bearer tokens sent to the APIs, by hash, and whether an API accepted them.
+static TOKENS: Mutex<BTreeMap<u64, bool>> = Mutex::new(BTreeMap::new());
+
+/// The settings of the client shared by every API call. Proxies are also read
+/// from the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables.
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    pub connect_timeout: Duration,
+    /// The timeout of a whole request, from connecting to reading the body.
+    pub timeout: Duration,
+    pub proxy: Option<String>,
+    /// A PEM bundle of extra trusted certificates, such as the one of a
+    /// TLS-intercepting proxy.
+    pub ca_bundle: Option<String>,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        return Self {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(300),
+            proxy: None,
+            ca_bundle: None,
+        };
+    }
+}
+
+/// Builds the shared client, once at startup. The settings also apply to the
+/// async client.
+pub fn init_client(settings: &ClientSettings) -> Result<(), String> {
+    let _ = CLIENT.set(build_client(settings)?);
+    let _ = SETTINGS.set(settings.clone());
+    return Ok(());
+}
+
+/// The client shared by every API call, with the default settings unless
+/// `init_client` was called.
+pub fn client() -> &'static Client {
+    return CLIENT.get_or_init(|| {
+        build_client(client_settings()).expect("Could not create the HTTP client")
+    });
+}
+
+pub(super) fn client_settings() -> &'static ClientSettings {
+    return SETTINGS.get_or_init(ClientSettings::default);
+}
+
+fn build_client(settings: &ClientSettings) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(settings.connect_timeout)
+        .timeout(settings.timeout);
+
+    if let Some(proxy) = proxy(settings)? {
+        builder = builder.proxy(proxy);
+    }
+
+    for certificate in certificates(settings)? {
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    return builder.build().map_err(|e| e.to_string());
+}
+
+pub(super) fn proxy(settings: &ClientSettings) -> Result<Option<Proxy>, String> {
+    let Some(proxy) = &settings.proxy else {
+        return Ok(None);
+    };
+
+    return Proxy::all(proxy)
+        .map(Some)
+        .map_err(|e| format!("Invalid proxy {}: {}", proxy, e));
+}
+
+/// The certificates of the CA bundle.
+pub(super) fn certificates(settings: &ClientSettings) -> Result<Vec<Certificate>, String> {
+    let Some(path) = &settings.ca_bundle else {
+        return Ok(vec![]);
+    };
+
+    let pem = fs::read(path).map_err(|e| format!("Failed to read CA bundle {}: {}", path, e))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+
+    if certificates.is_empty() {
+        return Err(format!("No certificate found in the CA bundle {}", path));
+    }
+
+    return Ok(certificates);
+}
+
+/// How the API calls are retried on transient failures. The delay doubles on
+/// every attempt up to `max_delay`, with full jitter, unless the server asks
+/// for a delay with `Retry-After`. A `Retry-After` longer than `max_delay` is
+/// not waited for, the call fails instead.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        };
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+
+        return delay.mul_f64(jitter as f64 / 1000.0);
+    }
+
+    /// How long to wait before trying the request again, if it is worth it.
+    pub(super) fn retry_delay(
+        &self,
+        attempt: u32,
+        is_idempotent: bool,
+        outcome: Result<(StatusCode, &HeaderMap), &reqwest::Error>,
+    ) -> Option<Duration> {
+        let delay = match outcome {
+            Ok((status, headers))
+                if status == StatusCode::TOO_MANY_REQUESTS
+                    || (is_idempotent && RETRYABLE_STATUSES.contains(&status)) =>
+            {
+                match retry_after(headers) {
+                    Some(delay) if delay > self.max_delay => {
+                        logging::log(
+                            1,
+                            "retry",
+                            &format!(
+                                "Not retrying, Retry-After of {}s exceeds the max delay",
+                                delay.as_secs()
+                            ),
+                            serde_json::json!({ "retry_after_ms": delay.as_millis() as u64 }),
+                        );
+                        return None;
+                    }
+                    Some(delay) => delay,
+                    None => self.backoff(attempt),
+                }
+            }
+            Err(e) if e.is_connect() || (is_idempotent && (e.is_timeout() || e.is_request())) => {
+                self.backoff(attempt)
+            }
+            _ => return None,
+        };
+
+        logging::log(
+            1,
+            "retry",
+            &format!(
+                "Retrying in {}ms, attempt {}",
+                delay.as_millis(),
+                attempt + 1
+            ),
+            serde_json::json!({ "delay_ms": delay.as_millis() as u64, "attempt": attempt + 1 }),
+        );
+
+        return Some(delay);
+    }
+}
+
+/// Sets the retry policy of every API call, once at startup.
+pub fn set_retry_policy(policy: RetryPolicy) {
+    let _ = RETRY_POLICY.set(policy);
+}
+
+pub(super) fn retry_policy() -> &'static RetryPolicy {
+    return RETRY_POLICY.get_or_init(RetryPolicy::default);
+}
+
+pub trait RetryingRequest {
+    /// Sends the request, retrying on transient failures. Requests with a
+    /// non-idempotent method are only retried when they surely were not
+    /// processed: on 429s and connection failures.
+    fn send_with_retry(self) -> Result<Response, reqwest::Error>;
+
+    /// Sends a request that is safe to repeat whatever its method, such as a
+    /// token exchange or a query, retrying on transient failures.
+    fn send_idempotent(self) -> Result<Response, reqwest::Error>;
+}
+
+impl RetryingRequest for RequestBuilder {
+    fn send_with_retry(self) -> Result<Response, reqwest::Error> {
+        let is_idempotent = self
+            .try_clone()
+            .and_then(|builder| builder.build().ok())
+            .is_some_and(|request| is_idempotent(request.method()));
+
+        return send(self, is_idempotent);
+    }
+
+    fn send_idempotent(self) -> Result<Response, reqwest::Error> {
+        return send(self, true);
+    }
+}
+
+pub(super) fn is_idempotent(method: &Method) -> bool {
+    return [
+        Method::GET,
+        Method::HEAD,
+        Method::PUT,
+        Method::DELETE,
+        Method::OPTIONS,
+    ]
+    .contains(method);
+}
+
+fn send(builder: RequestBuilder, is_idempotent: bool) -> Result<Response, reqwest::Error> {
+    let policy = retry_policy();
+    let token = builder
+        .try_clone()
+        .and_then(|builder| builder.build().ok())
+        .and_then(|request| token_hash(request.headers()));
+    let mut attempt = 0;
+
+    loop {
+        // Streamed bodies can't be cloned, these requests are sent once.
+        let Some(request) = builder.try_clone().filter(|_| attempt < policy.max_retries) else {
+            let result = execute(builder);
+            record_token(token, result.as_ref().map(|r| r.status()));
+            return result;
+        };
+
+        let result = execute(request);
+        record_token(token, result.as_ref().map(|r| r.status()));
+        let outcome = result.as_ref().map(|r| (r.status(), r.headers()));
+        let Some(delay) = policy.retry_delay(attempt, is_idempotent, outcome) else {
+            return result;
+        };
+
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// The hash of the bearer token of a request, if any.
+pub(super) fn token_hash(headers: &HeaderMap) -> Option<u64> {
+    let token = headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+
+    return Some(hash(token));
+}
+
+fn hash(token: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    return hasher.finish();
+}
+
+/// Remembers whether the API accepted the token of a request.
+pub(super) fn record_token(token: Option<u64>, status: Result<StatusCode, &reqwest::Error>) {
+    let (Some(token), Ok(status)) = (token, status) else {
+        return;
+    };
+
+    let mut tokens = TOKENS.lock().unwrap();
+    let is_accepted = tokens.entry(token).or_default();
+    *is_accepted |= status != StatusCode::UNAUTHORIZED;
+}
+
+/// Whether the APIs answered the token with a 401 without ever accepting it.
+/// Nothing was done with such a token, so a call that failed with it can be
+/// made again from the start with a new one.
+pub fn is_rejected_unused(token: &str) -> bool {
+    return TOKENS.lock().unwrap().get(&hash(token)) == Some(&false);
+}
+
+/// Sends the request once, logging it.
+fn execute(builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+    if !logging::enabled(1) {
+        return capture::execute(builder);
+    }
+
+    let request = builder.try_clone().and_then(|b| b.build().ok());
+    let (method, url) =
+        logging::request(request.as_ref().map(|r| (r.method(), r.url(), r.headers())));
+
+    let start = Instant::now();
+    let result = capture::execute(builder);
+    let outcome = result.as_ref().map(|r| (r.status(), r.headers()));
+    logging::response(&method, &url, outcome, start.elapsed());
+
+    return result;
+}
+
+/// Reads the `Retry-After` header, in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+
+    return (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn policy() -> RetryPolicy {
+        return RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+    }
+
+    fn headers(retry_after: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(value) = retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        }
+
+        return headers;
+    }
+
+    fn delay(is_idempotent: bool, status: u16, retry_after: Option<&str>) -> Option<Duration> {
+        let status = StatusCode::from_u16(status).unwrap();
+
+        return policy().retry_delay(0, is_idempotent, Ok((status, &headers(retry_after))));
+    }
+
+    #[test]
+    fn throttling_is_retried_whatever_the_method() {
+        assert!(delay(true, 429, None).is_some());
+        assert!(delay(false, 429, None).is_some());
+    }
+
+    #[test]
+    fn server_errors_are_retried_for_idempotent_methods_only() {
+        for status in [500, 502, 503, 504] {
+            assert!(delay(true, status, None).is_some(), "{}", status);
+            assert_eq!(delay(false, status, None), None, "{}", status);
+        }
+    }
+
+    #[test]
+    fn other_statuses_are_not_retried() {
+        for status in [200, 201, 400, 401, 403, 404, 409, 501] {
+            assert_eq!(delay(true, status, None), None, "{}", status);
+        }
+    }
+
+    #[test]
+    fn connection_failures_are_retried_whatever_the_method() {
+        let error = reqwest::blocking::Client::new()
+            .post("http://127.0.0.1:1")
+            .send()
+            .unwrap_err();
+        assert!(error.is_connect());
+
+        assert!(policy().retry_delay(0, false, Err(&error)).is_some());
+        assert!(policy().retry_delay(0, true, Err(&error)).is_some());
+    }
+
+    #[test]
+    fn invalid_requests_are_not_retried() {
+        let error = reqwest::blocking::get("not a url").unwrap_err();
+
+        assert_eq!(policy().retry_delay(0, true, Err(&error)), None);
+    }
+
+    #[test]
+    fn backoff_is_jittered_below_the_doubled_delay() {
+        let policy = policy();
+
+        for attempt in 0..8 {
+            let bound = (policy.base_delay * 2u32.pow(attempt)).min(policy.max_delay);
+
+            for _ in 0..50 {
+                assert!(policy.backoff(attempt) < bound, "attempt {}", attempt);
+            }
+        }
+
+        assert!(policy.backoff(u32::MAX) < policy.max_delay);
+    }
+
+    #[test]
+    fn retry_after_in_seconds_is_followed() {
+        assert_eq!(delay(true, 503, Some("1")), Some(Duration::from_secs(1)));
+        assert_eq!(delay(false, 429, Some(" 0 ")), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_as_a_date_is_followed() {
+        let date = (chrono::Utc::now() + chrono::TimeDelta::seconds(1)).to_rfc2822();
+
+        let delay = delay(true, 429, Some(&date)).unwrap();
+
+        assert!(delay <= Duration::from_secs(1), "{:?}", delay);
+    }
+
+    #[test]
+    fn retry_after_above_the_max_delay_fails() {
+        assert_eq!(delay(true, 429, Some("2")), None);
+
+        let date = (chrono::Utc::now() + chrono::TimeDelta::minutes(5)).to_rfc2822();
+        assert_eq!(delay(true, 503, Some(&date)), None);
+    }
+
+    #[test]
+    fn invalid_retry_after_falls_back_to_the_backoff() {
+        let delay = delay(true, 503, Some("soon")).unwrap();
+
+        assert!(delay < policy().base_delay);
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[test]
+    fn throttled_requests_are_sent_again() {
+        use tiny_http::{Header, Response, Server};
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", server.server_addr());
+        let responder = thread::spawn(move || {
+            let throttled = Response::from_string("")
+                .with_status_code(429)
+                .with_header(Header::from_bytes("Retry-After", "0").unwrap());
+            server.recv().unwrap().respond(throttled).unwrap();
+            server
+                .recv()
+                .unwrap()
+                .respond(Response::from_string("{}"))
+                .unwrap();
+        });
+
+        let response = client().post(url).body("{}").send_with_retry().unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        responder.join().unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use super::capture::{redact_headers, redact_url};
+use chrono::{SecondsFormat, Utc};
+use reqwest::{header::HeaderMap, Method, StatusCode, Url};
+use serde_json::{json, Map, Value};
+use std::{sync::OnceLock, time::Duration};
+
+/// The response headers identifying a call, to quote when opening a ticket
+/// with Apple or Google.
+pub const REQUEST_ID_HEADERS: [&str; 4] = [
+    "x-request-id",
+    "x-apple-jingle-correlation-key",
+    "x-apple-request-uuid",
+    "x-goog-request-id",
+];
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// What is logged: nothing by default, the API calls, retries and token
+/// exchanges with `-v`, and their headers too with `-vv`. Always
+/// written to stderr, so the output of the commands stays parseable.
+#[derive(Debug)]
+pub struct Logger {
+    pub verbosity: u8,
+    pub format: LogFormat,
+}
+
+/// Sets the logger, once at startup.
+pub fn set_logger(logger: Logger) {
+    let _ = LOGGER.set(logger);
+}
+
+pub fn enabled(verbosity: u8) -> bool {
+    return LOGGER
+        .get()
+        .is_some_and(|logger| logger.verbosity >= verbosity);
+}
+
+/// Logs an event with a human readable message and its fields, when the
+/// verbosity is at least `verbosity`.
+pub fn log(verbosity: u8, event: &str, message: &str, fields: Value) {
+    let Some(logger) = LOGGER.get().filter(|l| l.verbosity >= verbosity) else {
+        return;
+    };
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    match logger.format {
+        LogFormat::Text => eprintln!("{} {:<8} {}", timestamp, event, message),
+        LogFormat::Json => {
+            let mut line = Map::new();
+            line.insert("timestamp".to_string(), json!(timestamp));
+            line.insert("event".to_string(), json!(event));
+            line.insert("message".to_string(), json!(message));
+
+            if let Value::Object(fields) = fields {
+                line.extend(fields);
+            }
+
+            eprintln!("{}", Value::Object(line));
+        }
+    }
+}
+
+/// Logs the headers of a request about to be sent, returning its method and
+/// redacted URL for `response`.
+pub fn request(request: Option<(&Method, &Url, &HeaderMap)>) -> (String, String) {
+    let Some((method, url, headers)) = request else {
+        return ("?".to_string(), "?".to_string());
+    };
+
+    self::headers("request", &redact_headers(headers));
+
+    return (method.to_string(), redact_url(url));
+}
+
+/// Logs the response of a request, or its failure.
+pub fn response(
+    method: &str,
+    url: &str,
+    outcome: Result<(StatusCode, &HeaderMap), &reqwest::Error>,
+    latency: Duration,
+) {
+    match outcome {
+        Ok((status, headers)) => {
+            api_call(method, url, &Ok((status.as_u16(), headers)), latency);
+            self::headers("response", &redact_headers(headers));
+        }
+        Err(e) => api_call(method, url, &Err(e.to_string()), latency),
+    }
+}
+
+/// Logs a completed API call, or its failure to get a response.
+fn api_call(
+    method: &str,
+    url: &str,
+    result: &Result<(u16, &HeaderMap), String>,
+    latency: Duration,
+) {
+    if !enabled(1) {
+        return;
+    }
+
+    let latency_ms = latency.as_millis() as u64;
+
+    match result {
+        Ok((status, headers)) => {
+            let request_ids = request_ids(headers);
+            let ids = request_ids
+                .iter()
+                .map(|(name, value)| format!(" {}={}", name, value))
+                .collect::<String>();
+
+            log(
+                1,
+                "api",
+                &format!("{} {} {} {}ms{}", method, url, status, latency_ms, ids),
+                json!({
+                    "method": method,
+                    "url": url,
+                    "status": status,
+                    "latency_ms": latency_ms,
+                    "request_ids": request_ids
+                        .into_iter()
+                        .map(|(name, value)| (name, json!(value)))
+                        .collect::<Map<_, _>>(),
+                }),
+            );
+        }
+        Err(e) => log(
+            1,
+            "api",
+            &format!("{} {} failed after {}ms: {}", method, url, latency_ms, e),
+            json!({
+                "method": method,
+                "url": url,
+                "latency_ms": latency_ms,
+                "error": e,
+            }),
+        ),
+    }
+}
+
+/// Logs the headers of a request or response, with `-vv`.
+pub fn headers(event: &str, headers: &[(String, String)]) {
+    if !enabled(2) {
+        return;
+    }
+
+    let message = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    log(
+        2,
+        event,
+        &message,
+        json!({
+            "headers": headers
+                .iter()
+                .map(|(name, value)| (name.clone(), json!(value)))
+                .collect::<Map<_, _>>(),
+        }),
+    );
+}
+
+/// The request id headers of a response.
+pub fn request_ids(headers: &HeaderMap) -> Vec<(String, String)> {
+    return REQUEST_ID_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+}
@@ -1,3 +1,19 @@
+#[cfg(feature = "ios")]
 pub mod app_store_datasource;
+#[cfg(all(feature = "async", feature = "ios"))]
+pub mod async_app_store_datasource;
+#[cfg(all(feature = "async", feature = "android"))]
+pub mod async_google_play_datasource;
+#[cfg(feature = "async")]
+pub mod async_http;
+pub mod capture;
 pub mod datasource;
+pub mod endpoints;
+#[cfg(feature = "android")]
 pub mod google_play_datasource;
+pub mod http;
+pub mod logging;
+#[cfg(feature = "android")]
+pub mod play_reporting_datasource;
+#[cfg(feature = "ios")]
+pub mod report;
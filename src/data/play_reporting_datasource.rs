@@ -0,0 +1,197 @@
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::data::{
+    datasource::ResponseMapper,
+    endpoints::endpoints,
+    http::{client, RetryingRequest},
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TimeZone {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+
+    #[serde(rename = "timeZone")]
+    pub time_zone: Option<TimeZone>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Freshness {
+    #[serde(rename = "aggregationPeriod")]
+    pub aggregation_period: String,
+
+    #[serde(rename = "latestEndTime")]
+    pub latest_end_time: DateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FreshnessInfo {
+    #[serde(default)]
+    pub freshnesses: Vec<Freshness>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MetricSet {
+    #[serde(rename = "freshnessInfo")]
+    pub freshness_info: FreshnessInfo,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DimensionValue {
+    pub dimension: String,
+
+    #[serde(rename = "stringValue")]
+    pub string_value: Option<String>,
+
+    #[serde(rename = "int64Value")]
+    pub int64_value: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DecimalValue {
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MetricValue {
+    pub metric: String,
+
+    #[serde(rename = "decimalValue")]
+    pub decimal_value: Option<DecimalValue>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MetricsRow {
+    #[serde(default)]
+    pub dimensions: Vec<DimensionValue>,
+
+    #[serde(default)]
+    pub metrics: Vec<MetricValue>,
+}
+
+impl MetricsRow {
+    pub fn dimension(&self, name: &str) -> Option<String> {
+        return self
+            .dimensions
+            .iter()
+            .find(|d| d.dimension == name)
+            .and_then(|d| d.string_value.clone().or(d.int64_value.clone()));
+    }
+
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        return self
+            .metrics
+            .iter()
+            .find(|m| m.metric == name)
+            .and_then(|m| m.decimal_value.as_ref())
+            .and_then(|v| v.value.parse().ok());
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryMetricSetResponse {
+    #[serde(default)]
+    pub rows: Vec<MetricsRow>,
+
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+/// A query of the daily metrics of a metric set, such as `crashRateMetricSet`.
+#[derive(Debug)]
+pub struct MetricSetQuery<'a> {
+    pub metric_set: &'a str,
+    pub metrics: &'a [&'a str],
+    pub dimensions: &'a [&'a str],
+    pub filter: Option<&'a str>,
+}
+
+/// The Play Developer Reporting API, which serves the Android vitals.
+pub struct PlayReportingDataSource {}
+
+impl PlayReportingDataSource {
+    pub fn get_metric_set(
+        token: &str,
+        package_name: &str,
+        metric_set: &str,
+    ) -> Result<MetricSet, String> {
+        let endpoint = format!(
+            "{}/apps/{}/{}",
+            endpoints().play_reporting,
+            package_name,
+            metric_set
+        );
+
+        return client()
+            .get(endpoint)
+            .bearer_auth(token)
+            .send_with_retry()
+            .res::<MetricSet>()
+            .map_err(|e| format!("Failed to get {}: {}", metric_set, e));
+    }
+
+    /// Queries the metrics between `start` inclusive and `end` exclusive, going
+    /// through every page.
+    pub fn query_metric_set(
+        token: &str,
+        package_name: &str,
+        query: &MetricSetQuery,
+        start: &DateTime,
+        end: &DateTime,
+    ) -> Result<Vec<MetricsRow>, String> {
+        let endpoint = format!(
+            "{}/apps/{}/{}:query",
+            endpoints().play_reporting,
+            package_name,
+            query.metric_set
+        );
+
+        let mut rows: Vec<MetricsRow> = vec![];
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut body = json!({
+                "timelineSpec": {
+                    "aggregationPeriod": "DAILY",
+                    "startTime": start,
+                    "endTime": end,
+                },
+                "dimensions": query.dimensions,
+                "metrics": query.metrics,
+                "pageSize": 1000,
+            });
+
+            if let Some(filter) = query.filter {
+                body["filter"] = Value::String(filter.to_string());
+            }
+
+            if let Some(page_token) = &page_token {
+                body["pageToken"] = Value::String(page_token.clone());
+            }
+
+            let mut response = client()
+                .post(&endpoint)
+                .bearer_auth(token)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+                .send_idempotent()
+                .res::<QueryMetricSetResponse>()
+                .map_err(|e| format!("Failed to query {}: {}", query.metric_set, e))?;
+
+            rows.append(&mut response.rows);
+            page_token = response.next_page_token;
+
+            if page_token.is_none() {
+                return Ok(rows);
+            }
+        }
+    }
+}
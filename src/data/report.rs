@@ -0,0 +1,141 @@
+use flate2::read::MultiGzDecoder;
+use serde_json::{Map, Value};
+use std::io::Read;
+
+/// A tab-separated report as downloaded from App Store Connect.
+#[derive(Debug)]
+pub struct Report {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    /// Decodes a gzip compressed TSV payload, the first line being the headers.
+    pub fn from_gzip_tsv(bytes: &[u8]) -> Result<Self, String> {
+        let mut tsv = String::new();
+
+        MultiGzDecoder::new(bytes)
+            .read_to_string(&mut tsv)
+            .map_err(|e| format!("Failed to decompress report: {}", e))?;
+
+        return Report::from_tsv(&tsv);
+    }
+
+    /// Parses the first block of the TSV, anything after the first blank line, such
+    /// as the `Total_Rows` and `Total_Amount` footer of finance reports, is dropped.
+    pub fn from_tsv(tsv: &str) -> Result<Self, String> {
+        let mut lines = tsv
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .skip_while(|line| line.is_empty())
+            .take_while(|line| !line.is_empty());
+
+        let headers: Vec<String> = lines
+            .next()
+            .ok_or("The report is empty")?
+            .split('\t')
+            .map(|h| h.to_string())
+            .collect();
+
+        let rows = lines
+            .map(|line| line.split('\t').map(|v| v.to_string()).collect())
+            .collect();
+
+        return Ok(Report { headers, rows });
+    }
+
+    /// Maps each row to a JSON object keyed by the headers.
+    pub fn to_json(&self) -> Vec<Map<String, Value>> {
+        return self
+            .rows
+            .iter()
+            .map(|row| {
+                self.headers
+                    .iter()
+                    .zip(row.iter())
+                    .map(|(header, value)| (header.clone(), Value::String(value.clone())))
+                    .collect()
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const SALES: &str =
+        "Provider\tSKU\tUnits\r\nAPPLE\tcom.example.app\t3\r\nAPPLE\tcom.example.pro\t1\r\n";
+
+    #[test]
+    fn from_tsv_reads_the_headers_and_rows() {
+        let report = Report::from_tsv(SALES).unwrap();
+
+        assert_eq!(report.headers, vec!["Provider", "SKU", "Units"]);
+        assert_eq!(
+            report.rows,
+            vec![
+                vec!["APPLE", "com.example.app", "3"],
+                vec!["APPLE", "com.example.pro", "1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn from_tsv_ignores_blank_lines_around_the_report() {
+        let report = Report::from_tsv(&format!("\n{}\n\n", SALES)).unwrap();
+
+        assert_eq!(report.headers.len(), 3);
+        assert_eq!(report.rows.len(), 2);
+    }
+
+    #[test]
+    fn from_tsv_drops_the_finance_summary() {
+        let finance = "Start Date\tEnd Date\tPartner Share\n\
+            05/01/2024\t05/31/2024\t0.70\n\
+            \n\
+            Total_Rows\t1\n\
+            Total_Amount\t0.70\n";
+
+        let report = Report::from_tsv(finance).unwrap();
+
+        assert_eq!(
+            report.headers,
+            vec!["Start Date", "End Date", "Partner Share"]
+        );
+        assert_eq!(report.rows, vec![vec!["05/01/2024", "05/31/2024", "0.70"]]);
+    }
+
+    #[test]
+    fn from_tsv_rejects_an_empty_report() {
+        assert_eq!(
+            Report::from_tsv("\n\n").unwrap_err(),
+            "The report is empty".to_string()
+        );
+    }
+
+    #[test]
+    fn from_gzip_tsv_decompresses_the_report() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(SALES.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let report = Report::from_gzip_tsv(&bytes).unwrap();
+
+        assert_eq!(report.headers, vec!["Provider", "SKU", "Units"]);
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(
+            report.to_json()[1]["SKU"],
+            Value::String("com.example.pro".to_string())
+        );
+    }
+
+    #[test]
+    fn from_gzip_tsv_rejects_plain_text() {
+        assert!(Report::from_gzip_tsv(SALES.as_bytes())
+            .unwrap_err()
+            .starts_with("Failed to decompress report"));
+    }
+}
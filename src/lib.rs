@@ -0,0 +1,54 @@
+//! Manages app releases on the App Store and Google Play.
+//!
+//! The [`Store`](repositories::store::Store) trait holds the operations common
+//! to both stores, implemented by [`AppStore`](repositories::store::AppStore)
+//! and [`GooglePlay`](repositories::store::GooglePlay), which also expose the
+//! operations specific to their store. The raw API calls are in the data
+//! sources of the [`data`] module.
+//!
+//! Each platform is behind a cargo feature, `ios` and `android`, both enabled
+//! by default along with the `cli` feature building the `freitool` binary:
+//!
+//! ```toml
+//! freitool = { version = "0.1.0-alpha.3", default-features = false, features = ["ios"] }
+//! ```
+//!
+//! ```no_run
+//! # #[cfg(feature = "ios")]
+//! # fn main() -> Result<(), String> {
+//! use freitool::repositories::store::{AppStore, AppStoreSettings, Store};
+//!
+//! let mut store = AppStore::new(AppStoreSettings {
+//!     key_path: Some("AuthKey_XXXX.p8".to_string()),
+//!     issuer_id: Some("00000000-0000-0000-0000-000000000000".to_string()),
+//!     app_id: Some("1234567890".to_string()),
+//!     ..Default::default()
+//! })?;
+//! store.create_version("1.2.3")?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "ios"))]
+//! # fn main() {}
+//! ```
+//!
+//! The `mock-server` feature adds [`MockServer`](mock_server::MockServer), a
+//! fake of both APIs with in-memory state, to test against.
+//!
+//! The `async` feature adds `AsyncStore` and its implementations, in
+//! `repositories::async_store`, for services running on tokio.
+//!
+//! Every call goes through a client shared by the whole process. Its settings,
+//! retries, endpoints and logging are set once at startup with
+//! [`http::init_client`](data::http::init_client),
+//! [`http::set_retry_policy`](data::http::set_retry_policy),
+//! [`endpoints::set_endpoints`](data::endpoints::set_endpoints) and
+//! [`logging::set_logger`](data::logging::set_logger), the defaults being used
+//! otherwise.
+
+#![allow(clippy::needless_return)]
+
+pub mod config;
+pub mod data;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod repositories;
@@ -1,8 +1,30 @@
-use clap::{command, Arg, ArgAction, Command, ValueHint};
-use repositories::store::{self, AppStore, Store};
+#![allow(clippy::needless_return)]
 
-mod data;
-mod repositories;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{command, Arg, ArgAction, ArgMatches, Command, ValueHint};
+use freitool::config::{Config, KeyType, Profile};
+use freitool::data::app_store_datasource::{
+    BetaTesterAttributes, FinanceReportQuery, SalesReportQuery,
+};
+use freitool::data::capture::{self, Capture};
+use freitool::data::endpoints::{set_endpoints, Endpoints};
+use freitool::data::http::{self, ClientSettings, RetryPolicy};
+use freitool::data::logging::{self, LogFormat, Logger};
+use freitool::data::report::Report;
+use freitool::mock_server::MockServer;
+use freitool::repositories::batch::{AppStatus, Batch, BatchReport};
+use freitool::repositories::doctor::{self, Check, CheckStatus};
+use freitool::repositories::rollout::{parse_interval, AutoRollout, RolloutOutcome};
+use freitool::repositories::store::{
+    AppStore, AppStoreSettings, GooglePlay, GooglePlaySettings, ReviewFilter, Store,
+};
+use freitool::repositories::vitals::{
+    ANR_RATE, BAD_BEHAVIOR_THRESHOLDS, CRASH_RATE, EXCESSIVE_WAKEUP_RATE, SLOW_START_RATE,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod output;
 
 trait PlatformArguments {
     fn add_commands(self) -> Self;
@@ -10,43 +32,376 @@ trait PlatformArguments {
 
 impl PlatformArguments for Command {
     fn add_commands(self) -> Self {
+        return self
+            .subcommand(
+                Command::new("version")
+                    .subcommand_required(true)
+                    .subcommand_precedence_over_arg(true)
+                    .subcommand(
+                        Command::new("create")
+                            .about("Creates a new version")
+                            .subcommand_precedence_over_arg(true)
+                            .arg(
+                                Arg::new("name")
+                                    .help("The name of the version to be created")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("notes")
+                            .about("Updates the release notes")
+                            .subcommand_precedence_over_arg(true)
+                            .arg(
+                                Arg::new("message")
+                                    .help("The message")
+                                    .long("message")
+                                    .short('m')
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new("language")
+                                    .help("The language of the release notes")
+                                    .long("language")
+                                    .short('l')
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new("name")
+                                    .help("The version name to suffer the update")
+                                    .long("name")
+                                    .short('n')
+                                    .required(true),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("reviews")
+                    .about("Lists and replies to customer reviews")
+                    .subcommand_required(true)
+                    .subcommand_precedence_over_arg(true)
+                    .subcommand(
+                        Command::new("list")
+                            .about("Lists the customer reviews, newest first")
+                            .arg(
+                                Arg::new("rating")
+                                    .help("Only reviews with this star rating, can be repeated")
+                                    .long("rating")
+                                    .short('r')
+                                    .value_parser(clap::value_parser!(u8).range(1..=5))
+                                    .action(ArgAction::Append),
+                            )
+                            .arg(
+                                Arg::new("territory")
+                                    .help("Only reviews from this App Store territory, e.g. USA. Not available on Google Play")
+                                    .long("territory"),
+                            )
+                            .arg(
+                                Arg::new("language")
+                                    .help("Only reviews in this Google Play language, e.g. en")
+                                    .long("language")
+                                    .short('l'),
+                            )
+                            .arg(
+                                Arg::new("since")
+                                    .help("Only reviews created on or after this date")
+                                    .long("since")
+                                    .value_name("YYYY-MM-DD"),
+                            )
+                            .arg(
+                                Arg::new("until")
+                                    .help("Only reviews created before this date")
+                                    .long("until")
+                                    .value_name("YYYY-MM-DD"),
+                            )
+                            .arg(
+                                Arg::new("unanswered")
+                                    .help("Only reviews without a reply")
+                                    .long("unanswered")
+                                    .action(ArgAction::SetTrue),
+                            )
+                            .arg(
+                                Arg::new("format")
+                                    .help("The output format")
+                                    .long("format")
+                                    .short('f')
+                                    .value_parser(["table", "json", "csv"])
+                                    .default_value("table"),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("reply")
+                            .about("Replies to a customer review")
+                            .arg(
+                                Arg::new("id")
+                                    .help("The review id")
+                                    .long("id")
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new("message")
+                                    .help("The reply")
+                                    .long("message")
+                                    .short('m')
+                                    .required(true),
+                            ),
+                    ),
+            );
+    }
+}
+
+trait IosArguments {
+    fn add_ios_commands(self) -> Self;
+}
+
+impl IosArguments for Command {
+    fn add_ios_commands(self) -> Self {
         return self.subcommand(
-            Command::new("version")
+            Command::new("testflight")
+                .about("Manages TestFlight builds")
                 .subcommand_required(true)
                 .subcommand_precedence_over_arg(true)
                 .subcommand(
-                    Command::new("create")
-                        .about("Creates a new version")
-                        .subcommand_precedence_over_arg(true)
+                    Command::new("notes")
+                        .about("Updates the \"What to Test\" notes of a build")
                         .arg(
-                            Arg::new("name")
-                                .help("The name of the version to be created")
+                            Arg::new("build")
+                                .help("The build number")
+                                .long("build")
+                                .short('b')
                                 .required(true),
-                        ),
-                )
-                .subcommand(
-                    Command::new("notes")
-                        .about("Updates the release notes")
-                        .subcommand_precedence_over_arg(true)
+                        )
+                        .arg(
+                            Arg::new("locale")
+                                .help("The locale of the notes")
+                                .long("locale")
+                                .short('l')
+                                .required(true),
+                        )
                         .arg(
                             Arg::new("message")
                                 .help("The message")
                                 .long("message")
                                 .short('m')
                                 .required(true),
+                        ),
+                )
+                .subcommand(Command::new("groups").about("Lists the beta groups"))
+                .subcommand(
+                    Command::new("add-build")
+                        .about("Makes a build available to beta groups")
+                        .arg(
+                            Arg::new("build")
+                                .help("The build number")
+                                .long("build")
+                                .short('b')
+                                .required(true),
                         )
                         .arg(
-                            Arg::new("language")
-                                .help("The language of the release notes")
-                                .long("language")
-                                .short('l')
+                            Arg::new("group")
+                                .help("The beta group name or id, can be repeated")
+                                .long("group")
+                                .short('g')
+                                .action(ArgAction::Append)
                                 .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("invite")
+                        .about("Invites testers to beta groups")
+                        .args(tester_args())
+                        .arg(
+                            Arg::new("first-name")
+                                .help("The first name of the tester")
+                                .long("first-name")
+                                .conflicts_with("csv"),
                         )
                         .arg(
-                            Arg::new("name")
-                                .help("The version name to suffer the update")
-                                .long("name")
-                                .short('n')
+                            Arg::new("last-name")
+                                .help("The last name of the tester")
+                                .long("last-name")
+                                .conflicts_with("csv"),
+                        )
+                        .arg(
+                            Arg::new("group")
+                                .help("The beta group name or id, can be repeated")
+                                .long("group")
+                                .short('g')
+                                .action(ArgAction::Append)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Removes testers from beta groups, or from the app if no group is given")
+                        .args(tester_args())
+                        .arg(
+                            Arg::new("group")
+                                .help("The beta group name or id, can be repeated")
+                                .long("group")
+                                .short('g')
+                                .action(ArgAction::Append),
+                        ),
+                )
+                .subcommand(
+                    Command::new("submit")
+                        .about("Submits a build for beta app review")
+                        .arg(
+                            Arg::new("build")
+                                .help("The build number")
+                                .long("build")
+                                .short('b')
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("reports")
+                .about("Downloads App Store Connect reports")
+                .subcommand_required(true)
+                .subcommand_precedence_over_arg(true)
+                .arg(
+                    Arg::new("vendor-number")
+                        .global(true)
+                        .help("The vendor number of the account, defaults to the profile's")
+                        .long("vendor-number"),
+                )
+                .subcommand(
+                    Command::new("sales")
+                        .about("Downloads a sales and trends report")
+                        .arg(
+                            Arg::new("frequency")
+                                .help("The frequency of the report")
+                                .long("frequency")
+                                .value_parser(["DAILY", "WEEKLY", "MONTHLY", "YEARLY"])
+                                .default_value("DAILY"),
+                        )
+                        .arg(
+                            Arg::new("type")
+                                .help("The report type, e.g. SALES, SUBSCRIPTION or INSTALLS")
+                                .long("type")
+                                .default_value("SALES"),
+                        )
+                        .arg(
+                            Arg::new("subtype")
+                                .help("The report sub type, e.g. SUMMARY or DETAILED")
+                                .long("subtype")
+                                .default_value("SUMMARY"),
+                        )
+                        .arg(
+                            Arg::new("date")
+                                .help("The report date, YYYY-MM-DD, YYYY-MM or YYYY depending on the frequency, defaults to the latest")
+                                .long("date"),
+                        )
+                        .arg(
+                            Arg::new("report-version")
+                                .help("The version of the report format")
+                                .long("report-version")
+                                .default_value("1_0"),
+                        )
+                        .args(report_output_args()),
+                )
+                .subcommand(
+                    Command::new("finance")
+                        .about("Downloads a finance report")
+                        .arg(
+                            Arg::new("type")
+                                .help("The report type")
+                                .long("type")
+                                .value_parser(["FINANCIAL", "FINANCE_DETAIL"])
+                                .default_value("FINANCIAL"),
+                        )
+                        .arg(
+                            Arg::new("region")
+                                .help("The region code, defaults to ZZ for all regions, or Z1 for FINANCE_DETAIL")
+                                .long("region"),
+                        )
+                        .arg(
+                            Arg::new("month")
+                                .help("The fiscal month")
+                                .long("month")
+                                .value_name("YYYY-MM")
+                                .required(true),
+                        )
+                        .args(report_output_args()),
+                ),
+        );
+    }
+}
+
+trait AnalyticsArguments {
+    fn add_analytics_commands(self) -> Self;
+}
+
+impl AnalyticsArguments for Command {
+    fn add_analytics_commands(self) -> Self {
+        let granularity = Arg::new("granularity")
+            .help("Only instances with this granularity")
+            .long("granularity")
+            .value_parser(["DAILY", "WEEKLY", "MONTHLY"]);
+
+        let date = Arg::new("date")
+            .help("Only instances processed on this date")
+            .long("date")
+            .value_name("YYYY-MM-DD");
+
+        let report = Arg::new("report")
+            .help("The analytics report id")
+            .long("report")
+            .short('r')
+            .required(true);
+
+        return self.subcommand(
+            Command::new("analytics")
+                .about("Manages App Store Connect analytics reports")
+                .subcommand_required(true)
+                .subcommand_precedence_over_arg(true)
+                .subcommand(
+                    Command::new("request")
+                        .about("Requests analytics reports for the app, reusing an existing request")
+                        .arg(
+                            Arg::new("access-type")
+                                .help("ONGOING keeps generating reports, ONE_TIME_SNAPSHOT covers the past once")
+                                .long("access-type")
+                                .value_parser(["ONGOING", "ONE_TIME_SNAPSHOT"])
+                                .default_value("ONGOING"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("reports")
+                        .about("Lists the available analytics reports")
+                        .arg(
+                            Arg::new("category")
+                                .help("Only reports of this category")
+                                .long("category")
+                                .value_parser([
+                                    "APP_STORE_ENGAGEMENT",
+                                    "APP_STORE_COMMERCE",
+                                    "APP_USAGE",
+                                    "FRAMEWORK_USAGE",
+                                    "PERFORMANCE",
+                                ]),
+                        ),
+                )
+                .subcommand(
+                    Command::new("instances")
+                        .about("Lists the instances of an analytics report")
+                        .arg(report.clone())
+                        .arg(granularity.clone())
+                        .arg(date.clone()),
+                )
+                .subcommand(
+                    Command::new("download")
+                        .about("Downloads the segments of an analytics report, resuming previous downloads")
+                        .arg(report)
+                        .arg(granularity)
+                        .arg(date)
+                        .arg(
+                            Arg::new("dir")
+                                .help("The directory to download the segments to")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIR")
+                                .value_hint(ValueHint::DirPath)
                                 .required(true),
                         ),
                 ),
@@ -54,6 +409,974 @@ impl PlatformArguments for Command {
     }
 }
 
+fn report_output_args() -> [Arg; 2] {
+    return [
+        Arg::new("format")
+            .help("The output format")
+            .long("format")
+            .short('f')
+            .value_parser(["tsv", "csv", "json"])
+            .default_value("tsv"),
+        Arg::new("output")
+            .help("The file to write the report to, defaults to the standard output")
+            .long("output")
+            .short('o')
+            .value_name("FILE")
+            .value_hint(ValueHint::FilePath),
+    ];
+}
+
+trait AndroidArguments {
+    fn add_android_commands(self) -> Self;
+}
+
+impl AndroidArguments for Command {
+    fn add_android_commands(self) -> Self {
+        return self
+            .subcommand(
+                Command::new("testers")
+                    .about("Manages the Google Groups that can test the track")
+                    .subcommand_required(true)
+                    .subcommand_precedence_over_arg(true)
+                    .subcommand(Command::new("list").about("Lists the Google Groups of the track"))
+                    .subcommand(
+                        Command::new("set")
+                            .about("Replaces the Google Groups of the track")
+                            .arg(
+                                Arg::new("group")
+                                    .help("The Google Group email, can be repeated")
+                                    .long("group")
+                                    .short('g')
+                                    .action(ArgAction::Append)
+                                    .required(true),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("vitals")
+                    .about("Shows the crash, ANR, slow start and wakeup rates per version code")
+                    .arg(
+                        Arg::new("days")
+                            .help(
+                                "The number of days to average, ending at the latest day with data",
+                            )
+                            .long("days")
+                            .short('d')
+                            .value_parser(clap::value_parser!(u64).range(1..))
+                            .default_value("7"),
+                    )
+                    .arg(
+                        Arg::new("version-code")
+                            .help("Only this version code, can be repeated")
+                            .long("version-code")
+                            .action(ArgAction::Append),
+                    ),
+            )
+            .subcommand(
+                Command::new("release")
+                    .about("Manages the rollout of releases")
+                    .subcommand_required(true)
+                    .subcommand_precedence_over_arg(true)
+                    .subcommand(
+                        Command::new("auto-rollout")
+                            .about("Increases the rollout one step per run while the vitals stay below the thresholds, halting the release otherwise")
+                            .arg(
+                                Arg::new("steps")
+                                    .help("The user percentages of each step")
+                                    .long("steps")
+                                    .value_delimiter(',')
+                                    .value_parser(clap::value_parser!(f64))
+                                    .default_value("1,5,20,50,100"),
+                            )
+                            .arg(
+                                Arg::new("interval")
+                                    .help("The minimum time between steps, e.g. 30m, 24h or 2d")
+                                    .long("interval")
+                                    .default_value("24h"),
+                            )
+                            .arg(
+                                Arg::new("name")
+                                    .help("The release name, defaults to the release in progress or the draft")
+                                    .long("name")
+                                    .short('n'),
+                            )
+                            .arg(
+                                Arg::new("max-crash-rate")
+                                    .help("The maximum user-perceived crash rate, in percent")
+                                    .long("max-crash-rate")
+                                    .value_parser(clap::value_parser!(f64)),
+                            )
+                            .arg(
+                                Arg::new("max-anr-rate")
+                                    .help("The maximum user-perceived ANR rate, in percent")
+                                    .long("max-anr-rate")
+                                    .value_parser(clap::value_parser!(f64)),
+                            )
+                            .arg(
+                                Arg::new("vitals-days")
+                                    .help("The number of days of vitals to check")
+                                    .long("vitals-days")
+                                    .value_parser(clap::value_parser!(u64).range(1..))
+                                    .default_value("1"),
+                            )
+                            .arg(
+                                Arg::new("min-users")
+                                    .help("The distinct users of a day the vitals of the release must cover before each step")
+                                    .long("min-users")
+                                    .value_parser(clap::value_parser!(u64))
+                                    .default_value("1000"),
+                            )
+                            .arg(
+                                Arg::new("state-file")
+                                    .help("The file where the progress is kept between runs")
+                                    .long("state-file")
+                                    .value_name("FILE")
+                                    .value_hint(ValueHint::FilePath)
+                                    .default_value("freitool-rollout.json"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("countries")
+                    .about("Manages the countries where the track is available")
+                    .subcommand_required(true)
+                    .subcommand_precedence_over_arg(true)
+                    .subcommand(Command::new("list").about("Lists the countries of the track"))
+                    .subcommand(
+                        Command::new("set")
+                            .about("Targets the pending releases of the track to the countries")
+                            .arg(
+                                Arg::new("country")
+                                    .help("The ISO 3166 country code, can be repeated")
+                                    .long("country")
+                                    .short('c')
+                                    .action(ArgAction::Append)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new("rest-of-world")
+                                    .help("Also makes the releases available to new countries")
+                                    .long("rest-of-world")
+                                    .action(ArgAction::SetTrue),
+                            ),
+                    ),
+            );
+    }
+}
+
+fn tester_args() -> [Arg; 2] {
+    return [
+        Arg::new("email")
+            .help("The email of the tester")
+            .long("email")
+            .short('e')
+            .required_unless_present("csv")
+            .conflicts_with("csv"),
+        Arg::new("csv")
+            .help("A CSV file with an email, first name and last name header")
+            .long("csv")
+            .value_name("FILE")
+            .value_hint(ValueHint::FilePath),
+    ];
+}
+
+/// Reads testers from a CSV file, the columns are matched by header name so
+/// `email,first_name,last_name` and `First Name,Last Name,Email` both work.
+fn read_testers_csv(path: &str) -> Result<Vec<BetaTesterAttributes>, String> {
+    let reader = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+    return parse_testers_csv(reader, path);
+}
+
+fn parse_testers_csv<R: std::io::Read>(
+    mut reader: csv::Reader<R>,
+    path: &str,
+) -> Result<Vec<BetaTesterAttributes>, String> {
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|h| h.to_lowercase().replace([' ', '_'], ""))
+        .collect();
+
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let email = column("email").ok_or("The CSV file has no email column")?;
+    let first_name = column("firstname");
+    let last_name = column("lastname");
+
+    return reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| e.to_string())?;
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|i| record.get(i))
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+            };
+
+            let Some(email) = field(Some(email)) else {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                return Err(format!("{}: line {} has no email", path, line));
+            };
+
+            return Ok(BetaTesterAttributes {
+                email: Some(email),
+                first_name: field(first_name),
+                last_name: field(last_name),
+            });
+        })
+        .collect();
+}
+
+fn beta_testers(args: &ArgMatches) -> Result<Vec<BetaTesterAttributes>, String> {
+    if let Some(path) = args.get_one::<String>("csv") {
+        return read_testers_csv(path);
+    }
+
+    return Ok(vec![BetaTesterAttributes {
+        email: args.get_one::<String>("email").cloned(),
+        first_name: args
+            .try_get_one::<String>("first-name")
+            .ok()
+            .flatten()
+            .cloned(),
+        last_name: args
+            .try_get_one::<String>("last-name")
+            .ok()
+            .flatten()
+            .cloned(),
+    }]);
+}
+
+fn values(args: &ArgMatches, id: &str) -> Vec<String> {
+    return args
+        .get_many::<String>(id)
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+}
+
+fn arg_or(args: &ArgMatches, id: &str, default: &Option<String>) -> Option<String> {
+    return args.get_one::<String>(id).cloned().or(default.clone());
+}
+
+fn app_store(args: &ArgMatches, profile: &Profile) -> AppStore {
+    return AppStore::new(AppStoreSettings {
+        key_path: arg_or(args, "key-path", &profile.ios.key_path),
+        key_id: arg_or(args, "key-id", &profile.ios.key_id),
+        issuer_id: arg_or(args, "issuer-id", &profile.ios.issuer_id),
+        key_type: match args.get_flag("individual-key") {
+            true => KeyType::Individual,
+            false => profile.ios.key_type,
+        },
+        app_id: arg_or(args, "app-id", &profile.ios.app_id),
+        vendor_number: profile.ios.vendor_number.clone(),
+    })
+    .expect("Could not create the store");
+}
+
+fn google_play(args: &ArgMatches, profile: &Profile) -> GooglePlay {
+    return GooglePlay::new(GooglePlaySettings {
+        key_path: arg_or(args, "key-path", &profile.android.key_path),
+        package_name: arg_or(args, "package-name", &profile.android.package_name),
+        track: arg_or(args, "track", &profile.android.track),
+        token_cache: arg_or(args, "token-cache", &profile.android.token_cache)
+            .or_else(|| std::env::var("FREITOOL_TOKEN_CACHE").ok()),
+    })
+    .expect("Could not create the store");
+}
+
+fn version(store: &mut dyn Store, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "create" => {
+            let version = args.get_one::<String>("name").unwrap();
+            return store.create_version(version);
+        }
+
+        "notes" => {
+            let notes = args.get_one::<String>("message").unwrap();
+            let language = args.get_one::<String>("language").unwrap();
+            let version = args.get_one::<String>("name").unwrap();
+            return store.set_changelog(language, version, notes);
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn date(args: &ArgMatches, id: &str) -> Result<Option<DateTime<Utc>>, String> {
+    return args
+        .get_one::<String>(id)
+        .map(|value| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+                .map_err(|e| format!("Invalid --{} date: {}", id, e))
+        })
+        .transpose();
+}
+
+fn reviews(store: &mut dyn Store, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "list" => {
+            let filter = ReviewFilter {
+                ratings: args
+                    .get_many::<u8>("rating")
+                    .map(|values| values.copied().collect())
+                    .unwrap_or_default(),
+                territory: args.get_one::<String>("territory").cloned(),
+                language: args.get_one::<String>("language").cloned(),
+                since: date(args, "since")?,
+                until: date(args, "until")?,
+                unanswered: args.get_flag("unanswered"),
+            };
+
+            let reviews = store.reviews(&filter)?;
+            let format = match args.get_flag("machine") {
+                true => "json",
+                false => args.get_one::<String>("format").unwrap().as_str(),
+            };
+
+            if format != "table" {
+                return output::print_records(&reviews, format);
+            }
+
+            for review in reviews {
+                let stars =
+                    "★".repeat(review.rating as usize) + &"☆".repeat(5 - review.rating as usize);
+                let origin = review.territory.or(review.language).unwrap_or_default();
+
+                println!(
+                    "{} {} {} {} ({})",
+                    review.id,
+                    stars,
+                    review.created_at.format("%Y-%m-%d"),
+                    origin,
+                    review.author.unwrap_or_default()
+                );
+
+                if let Some(title) = review.title {
+                    println!("  {}", title);
+                }
+
+                println!("  {}", review.body.replace('\n', "\n  "));
+
+                if let Some(reply) = review.reply {
+                    println!("  > {}", reply.replace('\n', "\n  > "));
+                }
+
+                println!();
+            }
+
+            return Ok(());
+        }
+
+        "reply" => {
+            let id = args.get_one::<String>("id").unwrap();
+            let message = args.get_one::<String>("message").unwrap();
+            return store.reply_to_review(id, message);
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn write_report(report: &Report, args: &ArgMatches) -> Result<(), String> {
+    let format = match args.get_flag("machine") {
+        true => "json",
+        false => args.get_one::<String>("format").unwrap().as_str(),
+    };
+
+    match args.get_one::<String>("output") {
+        Some(path) => {
+            let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+            return output::write_report(report, format, file);
+        }
+        None => return output::write_report(report, format, std::io::stdout()),
+    }
+}
+
+fn reports(store: &mut AppStore, args: &ArgMatches) -> Result<(), String> {
+    if let Some(vendor_number) = args.get_one::<String>("vendor-number") {
+        store.vendor_number = Some(vendor_number.clone());
+    }
+
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "sales" => {
+            let query = SalesReportQuery {
+                frequency: args.get_one::<String>("frequency").unwrap().clone(),
+                report_type: args.get_one::<String>("type").unwrap().clone(),
+                report_sub_type: args.get_one::<String>("subtype").unwrap().clone(),
+                report_date: args.get_one::<String>("date").cloned(),
+                version: args.get_one::<String>("report-version").unwrap().clone(),
+            };
+
+            let report = store.sales_report(&query)?;
+            return write_report(&report, args);
+        }
+
+        "finance" => {
+            let report_type = args.get_one::<String>("type").unwrap().clone();
+            let is_detail = report_type == "FINANCE_DETAIL";
+            let region_code = match args.get_one::<String>("region") {
+                Some(region) if is_detail && region != "Z1" => {
+                    return Err("FINANCE_DETAIL reports only exist for the Z1 region".to_string());
+                }
+                Some(region) => region.clone(),
+                None if is_detail => "Z1".to_string(),
+                None => "ZZ".to_string(),
+            };
+
+            let query = FinanceReportQuery {
+                region_code,
+                report_date: args.get_one::<String>("month").unwrap().clone(),
+                report_type,
+            };
+
+            let report = store.finance_report(&query)?;
+            return write_report(&report, args);
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn analytics(store: &mut AppStore, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+    let machine = args.get_flag("machine");
+
+    match subcommand {
+        "request" => {
+            let access_type = args.get_one::<String>("access-type").unwrap();
+            let request = store.analytics_report_request(access_type)?;
+
+            if machine {
+                return output::print_json(&request);
+            }
+
+            println!("{}\t{}", request.id, request.attributes.access_type);
+            return Ok(());
+        }
+
+        "reports" => {
+            let category = args.get_one::<String>("category").map(|s| s.as_str());
+            let reports = store.analytics_reports(category)?;
+
+            if machine {
+                return output::print_json(&reports);
+            }
+
+            for report in reports {
+                println!(
+                    "{}\t{}\t{}",
+                    report.id, report.attributes.category, report.attributes.name
+                );
+            }
+
+            return Ok(());
+        }
+
+        "instances" => {
+            let instances = store.analytics_report_instances(
+                args.get_one::<String>("report").unwrap(),
+                args.get_one::<String>("granularity").map(|s| s.as_str()),
+                args.get_one::<String>("date").map(|s| s.as_str()),
+            )?;
+
+            if machine {
+                return output::print_json(&instances);
+            }
+
+            for instance in instances {
+                println!(
+                    "{}\t{}\t{}",
+                    instance.id,
+                    instance.attributes.granularity,
+                    instance.attributes.processing_date
+                );
+            }
+
+            return Ok(());
+        }
+
+        "download" => {
+            let downloads = store.download_analytics_report(
+                args.get_one::<String>("report").unwrap(),
+                args.get_one::<String>("granularity").map(|s| s.as_str()),
+                args.get_one::<String>("date").map(|s| s.as_str()),
+                Path::new(args.get_one::<String>("dir").unwrap()),
+            )?;
+
+            for download in &downloads {
+                let status = if download.skipped {
+                    "skipped"
+                } else {
+                    "downloaded"
+                };
+                println!("{}\t{}", status, download.path.display());
+            }
+
+            return Ok(());
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn testflight(store: &mut AppStore, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "notes" => {
+            let build = args.get_one::<String>("build").unwrap();
+            let locale = args.get_one::<String>("locale").unwrap();
+            let notes = args.get_one::<String>("message").unwrap();
+            return store.set_beta_changelog(locale, build, notes);
+        }
+
+        "groups" => {
+            let groups = store.beta_groups()?;
+
+            if args.get_flag("machine") {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&groups).map_err(|e| e.to_string())?
+                );
+            } else {
+                for group in groups {
+                    let kind = if group.attributes.is_internal_group {
+                        "internal"
+                    } else {
+                        "external"
+                    };
+                    println!("{}\t{}\t{}", group.id, group.attributes.name, kind);
+                }
+            }
+
+            return Ok(());
+        }
+
+        "add-build" => {
+            let build = args.get_one::<String>("build").unwrap();
+            return store.add_build_to_beta_groups(build, &values(args, "group"));
+        }
+
+        "invite" => {
+            return store.invite_beta_testers(&beta_testers(args)?, &values(args, "group"));
+        }
+
+        "remove" => {
+            let emails: Vec<String> = beta_testers(args)?
+                .into_iter()
+                .filter_map(|tester| tester.email)
+                .collect();
+            return store.remove_beta_testers(&emails, &values(args, "group"));
+        }
+
+        "submit" => {
+            let build = args.get_one::<String>("build").unwrap();
+            return store.submit_for_beta_review(build);
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn testers(store: &mut GooglePlay, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "list" => {
+            let groups = store.testers()?;
+
+            if args.get_flag("machine") {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&groups).map_err(|e| e.to_string())?
+                );
+            } else {
+                groups.iter().for_each(|group| println!("{}", group));
+            }
+
+            return Ok(());
+        }
+
+        "set" => {
+            return store.set_testers(&values(args, "group"));
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn android_vitals(store: &mut GooglePlay, args: &ArgMatches) -> Result<(), String> {
+    let version_codes = values(args, "version-code");
+    let mut vitals = store.vitals(*args.get_one::<u64>("days").unwrap())?;
+
+    if !version_codes.is_empty() {
+        vitals.retain(|v| version_codes.contains(&v.version_code));
+    }
+
+    if args.get_flag("machine") {
+        return output::print_json(&vitals);
+    }
+
+    let columns = [
+        ("Crash", CRASH_RATE),
+        ("ANR", ANR_RATE),
+        ("Slow start", SLOW_START_RATE),
+        ("Wakeups", EXCESSIVE_WAKEUP_RATE),
+    ];
+    let threshold = |metric: &str| {
+        BAD_BEHAVIOR_THRESHOLDS
+            .iter()
+            .find(|(m, _)| *m == metric)
+            .map(|(_, t)| *t)
+            .unwrap()
+    };
+
+    print!("{:<14}{:>10}", "Version code", "Users");
+    columns
+        .iter()
+        .for_each(|(label, _)| print!("{:>13}", label));
+    println!();
+
+    print!("{:<14}{:>10}", "Threshold", "");
+    columns
+        .iter()
+        .for_each(|(_, metric)| print!("{:>12.2}% ", threshold(metric) * 100.0));
+    println!();
+
+    for version in vitals {
+        print!(
+            "{:<14}{:>10.0}",
+            version.version_code, version.distinct_users
+        );
+
+        for (_, metric) in columns.iter() {
+            match version.rate(metric) {
+                Some(rate) => {
+                    let flag = if rate > threshold(metric) { "!" } else { " " };
+                    print!("{:>12.2}%{}", rate * 100.0, flag);
+                }
+                None => print!("{:>13}", "-"),
+            }
+        }
+
+        println!();
+    }
+
+    return Ok(());
+}
+
+fn release(store: &mut GooglePlay, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "auto-rollout" => {
+            let steps: Vec<f64> = args.get_many::<f64>("steps").unwrap().copied().collect();
+
+            if steps.iter().any(|s| *s <= 0.0 || *s > 100.0) || !steps.is_sorted() {
+                return Err("The steps must be ascending percentages between 0 and 100".to_string());
+            }
+
+            let threshold = |id: &str, metric: &'static str| {
+                let default = BAD_BEHAVIOR_THRESHOLDS
+                    .iter()
+                    .find(|(m, _)| *m == metric)
+                    .unwrap()
+                    .1;
+                let value = args
+                    .get_one::<f64>(id)
+                    .map(|v| v / 100.0)
+                    .unwrap_or(default);
+                return (metric, value);
+            };
+
+            let rollout = AutoRollout {
+                steps,
+                interval: parse_interval(args.get_one::<String>("interval").unwrap())?,
+                thresholds: vec![
+                    threshold("max-crash-rate", CRASH_RATE),
+                    threshold("max-anr-rate", ANR_RATE),
+                ],
+                vitals_days: *args.get_one::<u64>("vitals-days").unwrap(),
+                min_users: *args.get_one::<u64>("min-users").unwrap() as f64,
+                release: args.get_one::<String>("name").cloned(),
+                state_file: PathBuf::from(args.get_one::<String>("state-file").unwrap()),
+            };
+
+            match rollout.run(store)? {
+                RolloutOutcome::Started { percent } => {
+                    println!("Rollout started at {}%", percent);
+                }
+
+                RolloutOutcome::Advanced { percent } => {
+                    println!("Rollout increased to {}%", percent);
+                }
+
+                RolloutOutcome::Waiting { percent, next_at } => match next_at {
+                    Some(next_at) => {
+                        println!("Rollout at {}%, next step after {}", percent, next_at)
+                    }
+                    None => println!("Rollout at {}%, the last step", percent),
+                },
+
+                RolloutOutcome::AwaitingVitals { percent, users } => {
+                    println!(
+                        "Rollout at {}%, waiting for the vitals of more users ({} so far)",
+                        percent, users
+                    );
+                }
+
+                RolloutOutcome::Completed => {
+                    println!("Rollout completed");
+                }
+
+                RolloutOutcome::Halted { exceeded } => {
+                    let reasons: Vec<String> = exceeded
+                        .iter()
+                        .map(|(metric, rate)| format!("{} at {:.2}%", metric, rate * 100.0))
+                        .collect();
+
+                    if reasons.is_empty() {
+                        return Err("Rollout is halted".to_string());
+                    } else {
+                        return Err(format!("Rollout halted: {}", reasons.join(", ")));
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn countries(store: &mut GooglePlay, args: &ArgMatches) -> Result<(), String> {
+    let (subcommand, args) = args.subcommand().unwrap();
+
+    match subcommand {
+        "list" => {
+            let availability = store.country_availability()?;
+
+            if args.get_flag("machine") {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&availability).map_err(|e| e.to_string())?
+                );
+            } else {
+                availability
+                    .countries
+                    .iter()
+                    .for_each(|country| println!("{}", country.country_code));
+
+                if availability.rest_of_world {
+                    println!("Rest of world");
+                }
+            }
+
+            return Ok(());
+        }
+
+        "set" => {
+            return store.set_countries(&values(args, "country"), args.get_flag("rest-of-world"));
+        }
+
+        _ => {
+            unimplemented!("Command not implemented");
+        }
+    }
+}
+
+fn retry_policy(config: &Config, args: &ArgMatches) -> RetryPolicy {
+    let default = RetryPolicy::default();
+    let millis =
+        |value: Option<u64>, default: Duration| value.map(Duration::from_millis).unwrap_or(default);
+
+    return RetryPolicy {
+        max_retries: args
+            .get_one::<u32>("max-retries")
+            .copied()
+            .or(config.retry.max_retries)
+            .unwrap_or(default.max_retries),
+        base_delay: millis(
+            args.get_one::<u64>("retry-delay")
+                .copied()
+                .or(config.retry.base_delay_ms),
+            default.base_delay,
+        ),
+        max_delay: millis(config.retry.max_delay_ms, default.max_delay),
+    };
+}
+
+fn capture(args: &ArgMatches) -> Result<Option<Capture>, String> {
+    if let Some(dir) = args.get_one::<String>("record") {
+        return Capture::record(Path::new(dir)).map(Some);
+    }
+
+    if let Some(dir) = args.get_one::<String>("replay") {
+        return Capture::replay(Path::new(dir)).map(Some);
+    }
+
+    return Ok(None);
+}
+
+fn client_settings(config: &Config) -> ClientSettings {
+    let default = ClientSettings::default();
+    let http = &config.http;
+
+    return ClientSettings {
+        connect_timeout: http
+            .connect_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(default.connect_timeout),
+        timeout: http
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(default.timeout),
+        proxy: http
+            .proxy
+            .clone()
+            .or_else(|| std::env::var("FREITOOL_PROXY").ok()),
+        ca_bundle: http
+            .ca_bundle
+            .clone()
+            .or_else(|| std::env::var("FREITOOL_CA_BUNDLE").ok()),
+    };
+}
+
+fn endpoints(config: &Config) -> Endpoints {
+    let default = Endpoints::default();
+    let url = |value: &Option<String>, env: &str, default: String| {
+        value
+            .clone()
+            .or_else(|| std::env::var(env).ok())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or(default)
+    };
+
+    return Endpoints {
+        app_store_connect: url(
+            &config.endpoints.app_store_connect,
+            "FREITOOL_APP_STORE_CONNECT_URL",
+            default.app_store_connect,
+        ),
+        android_publisher: url(
+            &config.endpoints.android_publisher,
+            "FREITOOL_ANDROID_PUBLISHER_URL",
+            default.android_publisher,
+        ),
+        oauth_token: url(
+            &config.endpoints.oauth_token,
+            "FREITOOL_OAUTH_TOKEN_URL",
+            default.oauth_token,
+        ),
+        play_reporting: url(
+            &config.endpoints.play_reporting,
+            "FREITOOL_PLAY_REPORTING_URL",
+            default.play_reporting,
+        ),
+    };
+}
+
+fn doctor(config: &Config, args: &ArgMatches) -> Result<(), String> {
+    let mut checks: Vec<Check> = vec![];
+
+    for name in config.profile_names(args.get_one::<String>("profile").map(|s| s.as_str())) {
+        let profile = config.profile(Some(&name))?;
+        checks.append(&mut doctor::check_ios(&name, &profile.ios));
+        checks.append(&mut doctor::check_android(&name, &profile.android));
+    }
+
+    if args.get_flag("machine") {
+        output::print_json(&checks)?;
+    } else {
+        for check in &checks {
+            let mark = match check.status {
+                CheckStatus::Passed => "✓",
+                CheckStatus::Failed => "✗",
+                CheckStatus::Skipped => "-",
+            };
+
+            println!(
+                "{} [{} {}] {}: {}",
+                mark, check.profile, check.platform, check.name, check.detail
+            );
+
+            if let Some(fix) = check.fix {
+                println!("    Fix: {}", fix);
+            }
+        }
+    }
+
+    return doctor::status(&checks);
+}
+
+fn batch(config: &Config, args: &ArgMatches) -> Result<BatchReport, String> {
+    let batch = Batch::load(args.get_one::<String>("manifest").unwrap())?;
+    let profile = args.get_one::<String>("profile").map(|s| s.as_str());
+    batch.check_keys(config, profile)?;
+    let report = batch.run(
+        config,
+        profile,
+        args.get_one::<usize>("concurrency").copied(),
+    );
+
+    if let Some(path) = args.get_one::<String>("report") {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        std::fs::write(path, json + "\n")
+            .map_err(|e| format!("Failed to write report {}: {}", path, e))?;
+    }
+
+    if args.get_flag("machine") {
+        output::print_json(&report)?;
+        return Ok(report);
+    }
+
+    let width = report.apps.iter().map(|a| a.app.len()).max().unwrap_or(0);
+
+    for app in &report.apps {
+        let mark = match app.status {
+            AppStatus::Succeeded => "✓",
+            AppStatus::Failed => "✗",
+        };
+
+        println!(
+            "{} {:<width$} {:<7} {:>6.1}s {}",
+            mark,
+            app.app,
+            app.platform.name(),
+            app.duration_ms as f64 / 1000.0,
+            app.error.as_deref().unwrap_or_default(),
+            width = width
+        );
+    }
+
+    println!("{} succeeded, {} failed", report.succeeded, report.failed);
+
+    return Ok(report);
+}
+
 fn main() {
     let matches = command!()
         .propagate_version(true)
@@ -64,6 +1387,7 @@ fn main() {
         .subcommand(
             Command::new("android")
                 .add_commands()
+                .add_android_commands()
                 .arg(
                     Arg::new("package-name")
                         .help("The package name")
@@ -74,9 +1398,10 @@ fn main() {
                 .arg(
                     Arg::new("key-path")
                         .global(true)
-                        .help("The key path, must be a .json file")
-                        .value_name("FILE")
+                        .help("The .json key file, or env:NAME, base64env:NAME or - for stdin")
+                        .value_name("KEY")
                         .long("key-path")
+                        .visible_alias("key")
                         .value_hint(ValueHint::FilePath),
                 )
                 .arg(
@@ -85,11 +1410,59 @@ fn main() {
                         .help("The google play track")
                         .value_parser(["internal", "alpha", "beta", "production"])
                         .long("track"),
+                )
+                .arg(
+                    Arg::new("token-cache")
+                        .global(true)
+                        .help("Reuses the access tokens between runs, defaults to FREITOOL_TOKEN_CACHE")
+                        .value_name("FILE")
+                        .long("token-cache")
+                        .value_hint(ValueHint::FilePath),
                 ),
         )
+        .subcommand(
+            Command::new("mock-server")
+                .about("Runs a fake App Store Connect and Google Play API with in-memory state, for testing")
+                .arg(
+                    Arg::new("address")
+                        .help("The address to listen on, port 0 picks a free port")
+                        .long("address")
+                        .default_value("127.0.0.1:8080"),
+                ),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Runs an operation on every app of a manifest, several at a time")
+                .after_help("Exits with 1 when every app failed, 2 when some of them failed.")
+                .arg(
+                    Arg::new("manifest")
+                        .help("The .yaml manifest listing the operation and the apps")
+                        .required(true)
+                        .value_hint(ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .help("The apps processed at a time, overrides the manifest, defaults to 4")
+                        .value_parser(clap::value_parser!(usize))
+                        .long("concurrency"),
+                )
+                .arg(
+                    Arg::new("report")
+                        .help("Writes the JSON report to FILE")
+                        .value_name("FILE")
+                        .long("report")
+                        .value_hint(ValueHint::FilePath),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Checks the credentials and permissions of the configured profiles"),
+        )
         .subcommand(
             Command::new("ios")
                 .add_commands()
+                .add_ios_commands()
+                .add_analytics_commands()
                 .arg(
                     Arg::new("app-id")
                         .help("The App Store Connect app ID")
@@ -99,17 +1472,32 @@ fn main() {
                 .arg(
                     Arg::new("key-path")
                         .global(true)
-                        .help("The key path, must be a .p8 file")
-                        .value_name("FILE")
+                        .help("The .p8 key file, or env:NAME, base64env:NAME or - for stdin")
+                        .value_name("KEY")
                         .long("key-path")
+                        .visible_alias("key")
                         .value_hint(ValueHint::FilePath),
                 )
+                .arg(
+                    Arg::new("key-id")
+                        .global(true)
+                        .help("The key ID, defaults to the ID in the AuthKey_{ID}.p8 file name")
+                        .long("key-id"),
+                )
                 .arg(
                     Arg::new("issuer-id")
                         .global(true)
-                        .help("The issuer id, must be a valid UUID")
+                        .help("The issuer id, must be a valid UUID. Required unless --individual-key")
                         .long("issuer-id"),
-                ),
+                )
+                .arg(
+                    Arg::new("individual-key")
+                        .global(true)
+                        .help("Signs with an individual key, which has no issuer id")
+                        .long("individual-key")
+                        .conflicts_with("issuer-id")
+                        .action(ArgAction::SetTrue),
+                )
         )
         .arg(
             Arg::new("machine")
@@ -117,62 +1505,245 @@ fn main() {
                 .help("Prints the output in a machine-readable format")
                 .action(ArgAction::SetTrue)
                 .long("machine"),
+        )
+        .arg(
+            Arg::new("config")
+                .global(true)
+                .help("The configuration file, defaults to FREITOOL_CONFIG or ./freitool.yaml")
+                .value_name("FILE")
+                .long("config")
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("max-retries")
+                .global(true)
+                .help("Retries of the API calls failing with a transient error, defaults to 3")
+                .value_parser(clap::value_parser!(u32))
+                .long("max-retries"),
+        )
+        .arg(
+            Arg::new("retry-delay")
+                .global(true)
+                .help("The delay before the first retry in milliseconds, doubled on every retry")
+                .value_parser(clap::value_parser!(u64))
+                .value_name("MS")
+                .long("retry-delay"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .global(true)
+                .help("Logs the API calls to stderr, -vv also logs their headers")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count),
+        )
+        .arg(
+            Arg::new("log-format")
+                .global(true)
+                .help("The format of the logs")
+                .long("log-format")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("record")
+                .global(true)
+                .help("Saves every API request and response to DIR, with the secrets redacted")
+                .value_name("DIR")
+                .long("record")
+                .conflicts_with("replay")
+                .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("replay")
+                .global(true)
+                .help("Answers the API requests with the responses recorded in DIR, offline")
+                .value_name("DIR")
+                .long("replay")
+                .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("profile")
+                .global(true)
+                .help("The configuration profile, defaults to FREITOOL_PROFILE or default")
+                .long("profile"),
         );
 
     let matches = matches.get_matches();
     let (platform, args) = matches.subcommand().unwrap();
-    let (command, args) = args.subcommand().unwrap();
-
-    let mut store: Box<dyn Store> = match platform {
-        "ios" => Box::new(
-            AppStore::new(
-                args.get_one::<String>("key-path").map(|s| s.to_string()),
-                args.get_one::<String>("issuer-id").map(|s| s.to_string()),
-                args.get_one::<String>("ios-app-id").map(|s| s.to_string()),
-            )
-            .expect("Could not create the store"),
-        ),
 
-        "android" => Box::new(
-            store::GooglePlay::new(
-                args.get_one::<String>("key-path").map(|s| s.to_string()),
-                args.get_one::<String>("package-name")
-                    .map(|s| s.to_string()),
-                args.get_one::<String>("track").map(|s| s.to_string()),
-            )
-            .expect("Could not create the store"),
-        ),
+    if platform == "mock-server" {
+        let address = args.get_one::<String>("address").unwrap();
 
-        _ => {
-            unimplemented!("Platform is invalid");
+        if let Err(e) = MockServer::new().run(address) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
-    };
 
-    match command {
-        "version" => {
-            let (subcommand, args) = args.subcommand().unwrap();
+        return;
+    }
 
-            match subcommand {
-                "create" => {
-                    let version = args.get_one::<String>("name").unwrap();
-                    let _ = store.create_version(version).unwrap();
-                }
+    logging::set_logger(Logger {
+        verbosity: args.get_count("verbose"),
+        format: match args.get_one::<String>("log-format").map(|s| s.as_str()) {
+            Some("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        },
+    });
 
-                "notes" => {
-                    let notes = args.get_one::<String>("message").unwrap();
-                    let language = args.get_one::<String>("language").unwrap();
-                    let version = args.get_one::<String>("name").unwrap();
-                    let _ = store.set_changelog(language, version, notes).unwrap();
-                }
+    let config =
+        Config::load(args.get_one::<String>("config").map(|s| s.as_str())).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        });
 
-                _ => {
-                    unimplemented!("Command not implemented");
-                }
+    http::set_retry_policy(retry_policy(&config, args));
+    set_endpoints(endpoints(&config));
+
+    if let Err(e) = http::init_client(&client_settings(&config)) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    match capture(args) {
+        Ok(Some(capture)) => capture::set_capture(capture),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if platform == "batch" {
+        match batch(&config, args) {
+            Ok(report) if report.exit_code() != 0 => std::process::exit(report.exit_code()),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
         }
 
+        return;
+    }
+
+    if platform == "doctor" {
+        if let Err(e) = doctor(&config, args) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let (command, args) = args.subcommand().unwrap();
+
+    let profile = config
+        .profile(args.get_one::<String>("profile").map(|s| s.as_str()))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        });
+
+    let result = match (platform, command) {
+        ("ios", "testflight") => testflight(&mut app_store(args, &profile), args),
+        ("ios", "version") => version(&mut app_store(args, &profile), args),
+        ("ios", "reviews") => reviews(&mut app_store(args, &profile), args),
+        ("ios", "reports") => reports(&mut app_store(args, &profile), args),
+        ("ios", "analytics") => analytics(&mut app_store(args, &profile), args),
+        ("android", "reviews") => reviews(&mut google_play(args, &profile), args),
+        ("android", "version") => version(&mut google_play(args, &profile), args),
+        ("android", "testers") => testers(&mut google_play(args, &profile), args),
+        ("android", "countries") => countries(&mut google_play(args, &profile), args),
+        ("android", "vitals") => android_vitals(&mut google_play(args, &profile), args),
+        ("android", "release") => release(&mut google_play(args, &profile), args),
+
         _ => {
             panic!("This should not happen");
         }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The email, first name and last name of a tester.
+    type Tester = (String, Option<String>, Option<String>);
+
+    fn parse(content: &str) -> Result<Vec<Tester>, String> {
+        let reader = csv::Reader::from_reader(content.as_bytes());
+
+        return parse_testers_csv(reader, "testers.csv").map(|testers| {
+            testers
+                .into_iter()
+                .map(|t| (t.email.unwrap_or_default(), t.first_name, t.last_name))
+                .collect()
+        });
+    }
+
+    fn tester(email: &str, first: Option<&str>, last: Option<&str>) -> Tester {
+        return (
+            email.to_string(),
+            first.map(str::to_string),
+            last.map(str::to_string),
+        );
+    }
+
+    #[test]
+    fn testers_csv_matches_the_columns_by_header() {
+        let snake_case = parse("email,first_name,last_name\na@example.com,Ada,Lovelace\n");
+        let title_case = parse("First Name,Last Name,Email\nAda,Lovelace,a@example.com\n");
+
+        let expected = vec![tester("a@example.com", Some("Ada"), Some("Lovelace"))];
+        assert_eq!(snake_case, Ok(expected.clone()));
+        assert_eq!(title_case, Ok(expected));
+    }
+
+    #[test]
+    fn testers_csv_names_are_optional() {
+        let testers = parse("Email,First Name,Last Name\n a@example.com ,,\nb@example.com,Bo,\n");
+        let only_email = parse("email\nc@example.com\n");
+
+        assert_eq!(
+            testers,
+            Ok(vec![
+                tester("a@example.com", None, None),
+                tester("b@example.com", Some("Bo"), None),
+            ])
+        );
+        assert_eq!(only_email, Ok(vec![tester("c@example.com", None, None)]));
+    }
+
+    #[test]
+    fn testers_csv_skips_blank_lines() {
+        let testers = parse("email\na@example.com\n\n\nb@example.com\n\n");
+
+        assert_eq!(
+            testers,
+            Ok(vec![
+                tester("a@example.com", None, None),
+                tester("b@example.com", None, None),
+            ])
+        );
+    }
+
+    #[test]
+    fn testers_csv_rejects_a_row_without_email() {
+        let testers = parse("email,first_name\na@example.com,Ada\n ,Bo\n");
+
+        assert_eq!(testers, Err("testers.csv: line 3 has no email".to_string()));
+    }
+
+    #[test]
+    fn testers_csv_requires_an_email_column() {
+        let testers = parse("name\nAda\n");
+
+        assert_eq!(testers, Err("The CSV file has no email column".to_string()));
     }
 }
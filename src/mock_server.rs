@@ -0,0 +1,489 @@
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, thread};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// A fake of the App Store Connect and Google Play endpoints freitool uses, with
+/// in-memory state, to run the commands end to end without touching a real app.
+///
+/// The APIs are served under the same paths as the real ones, point freitool at
+/// them with the `FREITOOL_*_URL` variables printed on startup. Any token is
+/// accepted, but requests without one are answered with a 401. `GET /state`
+/// dumps the current state, for tests to assert on.
+#[derive(Debug, Default)]
+pub struct MockServer {
+    next_id: u64,
+    /// The committed tracks, keyed by package name, then by track name.
+    tracks: BTreeMap<String, BTreeMap<String, Value>>,
+    /// The committed testers, keyed by package name, then by track name.
+    testers: BTreeMap<String, BTreeMap<String, Value>>,
+    edits: BTreeMap<String, Edit>,
+    app_store_versions: Vec<Value>,
+    localizations: Vec<Value>,
+}
+
+/// A Google Play edit, a copy of the committed state applied on commit.
+#[derive(Debug)]
+struct Edit {
+    package_name: String,
+    tracks: BTreeMap<String, Value>,
+    testers: BTreeMap<String, Value>,
+}
+
+struct Reply {
+    status: u16,
+    body: Value,
+}
+
+fn reply(status: u16, body: Value) -> Reply {
+    return Reply { status, body };
+}
+
+fn not_found() -> Reply {
+    return reply(404, json!({ "error": "Not found" }));
+}
+
+fn bad_request(message: &str) -> Reply {
+    return reply(400, json!({ "error": message }));
+}
+
+/// Listens on `address`, returning the server and its URL.
+fn bind(address: &str) -> Result<(Server, String), String> {
+    let server = Server::http(address).map_err(|e| e.to_string())?;
+
+    match server.server_addr().to_ip() {
+        Some(address) => return Ok((server, format!("http://{}", address))),
+        None => return Err("The mock server must listen on an IP address".to_string()),
+    }
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Serves the requests until the process is stopped.
+    pub fn run(&mut self, address: &str) -> Result<(), String> {
+        let (server, url) = bind(address)?;
+
+        println!("Mock server listening on {}", url);
+        println!("export FREITOOL_APP_STORE_CONNECT_URL={}/v1", url);
+        println!(
+            "export FREITOOL_ANDROID_PUBLISHER_URL={}/androidpublisher/v3",
+            url
+        );
+        println!("export FREITOOL_OAUTH_TOKEN_URL={}/token", url);
+
+        self.serve(&server);
+
+        return Ok(());
+    }
+
+    /// Serves the requests on a background thread, returning the URL it listens
+    /// on. Port 0 picks a free port, for tests running side by side.
+    pub fn spawn(mut self, address: &str) -> Result<String, String> {
+        let (server, url) = bind(address)?;
+
+        thread::spawn(move || self.serve(&server));
+
+        return Ok(url);
+    }
+
+    fn serve(&mut self, server: &Server) {
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let reply = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => self.handle(&request, &body),
+                Err(e) => bad_request(&e.to_string()),
+            };
+
+            eprintln!("{} {} {}", request.method(), request.url(), reply.status);
+
+            let body = match reply.body {
+                Value::Null => String::new(),
+                body => body.to_string(),
+            };
+            let response = Response::from_string(body)
+                .with_status_code(reply.status)
+                .with_header(
+                    Header::from_bytes("Content-Type", "application/json").expect("Invalid header"),
+                );
+
+            if let Err(e) = request.respond(response) {
+                eprintln!("Failed to respond: {}", e);
+            }
+        }
+    }
+
+    fn handle(&mut self, request: &Request, body: &str) -> Reply {
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let is_authorized = request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Authorization") && h.value.as_str().starts_with("Bearer "));
+
+        match (request.method(), segments.as_slice()) {
+            (Method::Post, ["token"]) => {
+                return reply(
+                    200,
+                    json!({
+                        "access_token": "mock-access-token",
+                        "expires_in": 3600,
+                        "token_type": "Bearer",
+                    }),
+                )
+            }
+            (Method::Get, ["state"]) => return reply(200, self.state()),
+            _ if !is_authorized => return reply(401, json!({ "error": "Unauthorized" })),
+            (method, ["androidpublisher", "v3", "applications", package_name, rest @ ..]) => {
+                return self.android_publisher(method, package_name, rest, body)
+            }
+            (method, ["v1", rest @ ..]) => {
+                return self.app_store_connect(method, rest, &parse_query(query), body)
+            }
+            (Method::Get, ["playdeveloperreporting", "v1beta1", "apps", _, _]) => {
+                return reply(200, json!({ "freshnessInfo": { "freshnesses": [] } }))
+            }
+            _ => return not_found(),
+        }
+    }
+
+    fn state(&self) -> Value {
+        let edits: BTreeMap<&String, Value> = self
+            .edits
+            .iter()
+            .map(|(id, edit)| {
+                let edit = json!({
+                    "packageName": edit.package_name,
+                    "tracks": edit.tracks,
+                    "testers": edit.testers,
+                });
+                (id, edit)
+            })
+            .collect();
+
+        return json!({
+            "tracks": self.tracks,
+            "testers": self.testers,
+            "edits": edits,
+            "appStoreVersions": self.app_store_versions,
+            "appStoreVersionLocalizations": self.localizations,
+        });
+    }
+
+    fn next_id(&mut self) -> String {
+        self.next_id += 1;
+        return self.next_id.to_string();
+    }
+
+    fn android_publisher(
+        &mut self,
+        method: &Method,
+        package_name: &str,
+        path: &[&str],
+        body: &str,
+    ) -> Reply {
+        let action = path.last().and_then(|segment| segment.split_once(':'));
+
+        match (method, path, action) {
+            (Method::Post, ["edits"], _) => {
+                let id = self.next_id();
+                let edit = Edit {
+                    package_name: package_name.to_string(),
+                    tracks: self.tracks.get(package_name).cloned().unwrap_or_default(),
+                    testers: self.testers.get(package_name).cloned().unwrap_or_default(),
+                };
+
+                self.edits.insert(id.clone(), edit);
+
+                return reply(200, json!({ "id": id }));
+            }
+
+            (Method::Post, ["edits", _], Some((edit_id, "commit"))) => {
+                let Some(edit) = self.edits.remove(edit_id) else {
+                    return not_found();
+                };
+
+                self.tracks.insert(edit.package_name.clone(), edit.tracks);
+                self.testers.insert(edit.package_name, edit.testers);
+
+                return reply(200, json!({ "id": edit_id }));
+            }
+
+            (Method::Delete, ["edits", edit_id], _) => {
+                if self.edits.remove(*edit_id).is_none() {
+                    return not_found();
+                }
+
+                return reply(204, Value::Null);
+            }
+
+            (Method::Get, ["reviews"], _) => return reply(200, json!({ "reviews": [] })),
+
+            (Method::Post, ["reviews", _], Some((review_id, "reply"))) => {
+                let Ok(body) = serde_json::from_str::<Value>(body) else {
+                    return bad_request("Invalid JSON");
+                };
+
+                return reply(
+                    200,
+                    json!({
+                        "result": { "replyText": body["replyText"], "reviewId": review_id },
+                    }),
+                );
+            }
+
+            (method, ["edits", edit_id, resource, track], _) => {
+                let Some(edit) = self
+                    .edits
+                    .get_mut(*edit_id)
+                    .filter(|edit| edit.package_name == package_name)
+                else {
+                    return not_found();
+                };
+
+                return edit_resource(edit, method, resource, track, body);
+            }
+
+            _ => return not_found(),
+        }
+    }
+
+    fn app_store_connect(
+        &mut self,
+        method: &Method,
+        path: &[&str],
+        query: &BTreeMap<String, String>,
+        body: &str,
+    ) -> Reply {
+        match (method, path) {
+            (Method::Get, ["apps", app_id]) => {
+                return reply(
+                    200,
+                    json!({
+                        "data": {
+                            "type": "apps",
+                            "id": app_id,
+                            "attributes": { "name": "Mock app", "bundleId": "com.example.mock" },
+                        },
+                    }),
+                );
+            }
+
+            (
+                Method::Get,
+                ["apps", _, "betaGroups" | "customerReviews" | "analyticsReportRequests"],
+            ) => return reply(200, json!({ "data": [] })),
+
+            (Method::Get, ["apps", app_id, "appStoreVersions"]) => {
+                let version_filter = query.get("filter[versionString]");
+                let versions: Vec<&Value> = self
+                    .app_store_versions
+                    .iter()
+                    .filter(|v| v["relationships"]["app"]["data"]["id"] == *app_id)
+                    .filter(|v| {
+                        version_filter
+                            .is_none_or(|filter| v["attributes"]["versionString"] == **filter)
+                    })
+                    .collect();
+
+                return reply(200, json!({ "data": versions }));
+            }
+
+            (Method::Post, ["appStoreVersions"]) => {
+                let Ok(body) = serde_json::from_str::<Value>(body) else {
+                    return bad_request("Invalid JSON");
+                };
+
+                let data = &body["data"];
+                let app_id = &data["relationships"]["app"]["data"]["id"];
+                let version = &data["attributes"]["versionString"];
+
+                if !app_id.is_string() || !version.is_string() {
+                    return bad_request("Missing app or versionString");
+                }
+
+                let exists = self.app_store_versions.iter().any(|v| {
+                    v["relationships"]["app"]["data"]["id"] == *app_id
+                        && v["attributes"]["versionString"] == *version
+                });
+
+                if exists {
+                    return reply(
+                        409,
+                        json!({ "errors": [{ "detail": "Version already exists" }] }),
+                    );
+                }
+
+                let version_id = self.next_id();
+                let localization_id = self.next_id();
+                let version = json!({
+                    "type": "appStoreVersions",
+                    "id": version_id,
+                    "attributes": {
+                        "platform": data["attributes"]["platform"],
+                        "versionString": version,
+                        "appStoreState": "PREPARE_FOR_SUBMISSION",
+                    },
+                    "relationships": data["relationships"],
+                });
+
+                self.localizations.push(json!({
+                    "type": "appStoreVersionLocalizations",
+                    "id": localization_id,
+                    "attributes": { "locale": "en-US", "whatsNew": null },
+                    "relationships": {
+                        "appStoreVersion": { "data": { "type": "appStoreVersions", "id": version_id } },
+                    },
+                }));
+                self.app_store_versions.push(version.clone());
+
+                return reply(201, json!({ "data": version }));
+            }
+
+            (Method::Get, ["appStoreVersions", version_id, "appStoreVersionLocalizations"]) => {
+                let localizations: Vec<&Value> = self
+                    .localizations
+                    .iter()
+                    .filter(|l| l["relationships"]["appStoreVersion"]["data"]["id"] == *version_id)
+                    .collect();
+
+                return reply(200, json!({ "data": localizations }));
+            }
+
+            (Method::Patch, ["appStoreVersionLocalizations", localization_id]) => {
+                let Ok(body) = serde_json::from_str::<Value>(body) else {
+                    return bad_request("Invalid JSON");
+                };
+
+                let Some(localization) = self
+                    .localizations
+                    .iter_mut()
+                    .find(|l| l["id"] == *localization_id)
+                else {
+                    return not_found();
+                };
+
+                if let Some(attributes) = body["data"]["attributes"].as_object() {
+                    for (key, value) in attributes {
+                        localization["attributes"][key] = value.clone();
+                    }
+                }
+
+                return reply(200, json!({ "data": localization }));
+            }
+
+            _ => return not_found(),
+        }
+    }
+}
+
+/// Reads or replaces a track, or its testers, within an edit.
+fn edit_resource(
+    edit: &mut Edit,
+    method: &Method,
+    resource: &str,
+    track: &str,
+    body: &str,
+) -> Reply {
+    match (method, resource) {
+        (Method::Get, "tracks") => {
+            let default = json!({ "track": track, "releases": [] });
+            return reply(200, edit.tracks.get(track).cloned().unwrap_or(default));
+        }
+
+        (Method::Get, "testers") => {
+            let default = json!({ "googleGroups": [] });
+            return reply(200, edit.testers.get(track).cloned().unwrap_or(default));
+        }
+
+        (Method::Get, "countryAvailability") => {
+            let targeting = edit
+                .tracks
+                .get(track)
+                .and_then(|t| t["releases"].as_array())
+                .and_then(|releases| releases.iter().find_map(|r| r.get("countryTargeting")));
+
+            let Some(targeting) = targeting else {
+                return reply(200, json!({ "countries": [], "restOfWorld": true }));
+            };
+
+            let countries: Vec<Value> = targeting["countries"]
+                .as_array()
+                .map(|countries| {
+                    countries
+                        .iter()
+                        .map(|c| json!({ "countryCode": c }))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            return reply(
+                200,
+                json!({
+                    "countries": countries,
+                    "restOfWorld": targeting["includeRestOfWorld"],
+                }),
+            );
+        }
+
+        (Method::Put | Method::Patch, "tracks" | "testers") => {
+            let Ok(mut value) = serde_json::from_str::<Value>(body) else {
+                return bad_request("Invalid JSON");
+            };
+
+            let resources = match resource {
+                "tracks" => {
+                    value["track"] = json!(track);
+                    &mut edit.tracks
+                }
+                _ => &mut edit.testers,
+            };
+
+            resources.insert(track.to_string(), value.clone());
+
+            return reply(200, value);
+        }
+
+        _ => return not_found(),
+    }
+}
+
+/// Parses a query string, decoding the percent-encoded characters.
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    return query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+        .collect();
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    return String::from_utf8_lossy(&decoded).to_string();
+}
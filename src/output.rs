@@ -0,0 +1,85 @@
+use freitool::data::report::Report;
+use serde::Serialize;
+use std::io::{self, Write};
+
+pub fn print_json<T>(value: &T) -> Result<(), String>
+where
+    T: Serialize + ?Sized,
+{
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", json);
+
+    return Ok(());
+}
+
+/// Prints the records to the standard output as `json` or `csv`.
+pub fn print_records<T>(records: &[T], format: &str) -> Result<(), String>
+where
+    T: Serialize,
+{
+    match format {
+        "json" => {
+            print_json(records)?;
+        }
+
+        "csv" => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+
+            for record in records {
+                writer.serialize(record).map_err(|e| e.to_string())?;
+            }
+
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+
+        _ => {
+            return Err(format!("Unsupported output format: {}", format));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Writes the report as `tsv`, `csv` or `json`.
+pub fn write_report<W>(report: &Report, format: &str, mut writer: W) -> Result<(), String>
+where
+    W: Write,
+{
+    match format {
+        "tsv" => {
+            let mut writer = io::BufWriter::new(writer);
+
+            for line in std::iter::once(&report.headers).chain(report.rows.iter()) {
+                writeln!(writer, "{}", line.join("\t")).map_err(|e| e.to_string())?;
+            }
+
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+
+        "csv" => {
+            let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+
+            writer
+                .write_record(&report.headers)
+                .map_err(|e| e.to_string())?;
+
+            for row in &report.rows {
+                writer.write_record(row).map_err(|e| e.to_string())?;
+            }
+
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+
+        "json" => {
+            let json =
+                serde_json::to_string_pretty(&report.to_json()).map_err(|e| e.to_string())?;
+            writeln!(writer, "{}", json).map_err(|e| e.to_string())?;
+        }
+
+        _ => {
+            return Err(format!("Unsupported output format: {}", format));
+        }
+    }
+
+    return Ok(());
+}
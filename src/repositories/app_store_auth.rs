@@ -0,0 +1,285 @@
+use super::key_source::KeySource;
+use crate::config::KeyType;
+use crate::data::{app_store_datasource::AppStoreDataSource, http, logging};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The lifetime of the tokens, App Store Connect rejects tokens living longer than 20 minutes.
+const TOKEN_LIFETIME_SECONDS: usize = 60 * 5;
+
+/// Tokens are renewed this many seconds before they expire, so that a request
+/// never goes out with a token about to expire.
+const EXPIRY_MARGIN_SECONDS: usize = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// The issuer of team keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+
+    /// `user` for individual keys, which have no issuer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+
+    iat: usize,
+    exp: usize,
+    aud: String,
+}
+
+/// Signs the App Store Connect API tokens with the `.p8` key. Team keys are
+/// identified by their issuer, individual keys have none.
+pub struct AppStoreAuth {
+    pub key: KeySource,
+    pub key_id: Option<String>,
+    pub issuer_id: Option<String>,
+    pub key_type: KeyType,
+    token: Option<String>,
+    token_expiration: Option<usize>,
+}
+
+impl AppStoreAuth {
+    pub fn new(
+        key: KeySource,
+        key_id: Option<String>,
+        issuer_id: Option<String>,
+        key_type: KeyType,
+    ) -> Self {
+        return Self {
+            key,
+            key_id,
+            issuer_id,
+            key_type,
+            token: None,
+            token_expiration: None,
+        };
+    }
+
+    /// Calls the API with a data source holding a valid token, see `with_token`.
+    pub fn with_data_source<T>(
+        &mut self,
+        call: impl Fn(&AppStoreDataSource) -> Result<T, String>,
+    ) -> Result<T, String> {
+        return self.with_token(|token| call(&AppStoreDataSource::new(token.to_string())));
+    }
+
+    /// Calls the API with a token, signing a new one before the current one expires.
+    /// The call is made again with a new token only if the API rejected the token
+    /// on its first request, as the earlier requests of a call are not repeated.
+    pub fn with_token<T>(&mut self, call: impl Fn(&str) -> Result<T, String>) -> Result<T, String> {
+        let token = self.token()?;
+
+        match call(&token) {
+            Err(_) if http::is_rejected_unused(&token) => {
+                self.revoke();
+                return call(&self.token()?);
+            }
+            result => return result,
+        }
+    }
+
+    /// Forgets the token, so that the next call signs a new one.
+    pub fn revoke(&mut self) {
+        self.token = None;
+    }
+
+    pub fn token(&mut self) -> Result<String, String> {
+        if !self.is_logged_in() {
+            self.login()?;
+        }
+
+        return Ok(self.token.clone().unwrap());
+    }
+
+    fn login(&mut self) -> Result<(), String> {
+        let key_id = self.key_id()?;
+        let (iss, sub) = self.issuer()?;
+
+        logging::log(
+            1,
+            "auth",
+            &format!("Signing an App Store Connect token with key {}", key_id),
+            serde_json::json!({ "key_id": key_id }),
+        );
+
+        let now = now();
+        let expiration = now + TOKEN_LIFETIME_SECONDS;
+
+        let claims = Claims {
+            iss,
+            sub,
+            iat: now,
+            exp: expiration,
+            aud: "appstoreconnect-v1".to_string(),
+        };
+
+        let token = encode(
+            &Header {
+                alg: jsonwebtoken::Algorithm::ES256,
+                typ: Some("JWT".to_string()),
+                kid: Some(key_id.to_string()),
+                ..Default::default()
+            },
+            &claims,
+            &self.encoding_key()?,
+        )
+        .map_err(|e| e.to_string())?;
+
+        self.token_expiration = Some(expiration);
+        self.token = Some(token);
+
+        Ok(())
+    }
+
+    /// Parses the `.p8` key.
+    pub fn encoding_key(&self) -> Result<EncodingKey, String> {
+        return EncodingKey::from_ec_pem(self.key.read()?.as_bytes())
+            .map_err(|e| format!("Invalid .p8 key {}: {}", self.key, e));
+    }
+
+    /// The given key ID, or the one in the `AuthKey_{ID}.p8` key file name.
+    pub fn key_id(&self) -> Result<String, String> {
+        if let Some(key_id) = &self.key_id {
+            return Ok(key_id.clone());
+        }
+
+        return self
+            .key
+            .path()
+            .and_then(|path| Path::new(path).file_name()?.to_str())
+            .and_then(|name| name.strip_prefix("AuthKey_")?.strip_suffix(".p8"))
+            .filter(|key_id| !key_id.is_empty())
+            .map(|key_id| key_id.to_string())
+            .ok_or(format!(
+                "The key ID can't be read from {}, use --key-id or name the key file AuthKey_{{ID}}.p8",
+                self.key
+            ));
+    }
+
+    /// The `iss` and `sub` claims of the key type.
+    pub fn issuer(&self) -> Result<(Option<String>, Option<String>), String> {
+        match (self.key_type, &self.issuer_id) {
+            (KeyType::Team, Some(issuer_id)) => return Ok((Some(issuer_id.clone()), None)),
+            (KeyType::Team, None) => {
+                return Err("Issuer ID is required, unless the key is an individual key".to_string())
+            }
+            (KeyType::Individual, None) => return Ok((None, Some("user".to_string()))),
+            (KeyType::Individual, Some(_)) => {
+                return Err("Individual keys have no issuer ID".to_string())
+            }
+        }
+    }
+
+    fn is_logged_in(&self) -> bool {
+        let is_expired: bool = self
+            .token_expiration
+            .map(|exp| exp < now() + EXPIRY_MARGIN_SECONDS)
+            .unwrap_or(true);
+
+        return self.token.is_some() && !is_expired;
+    }
+}
+
+fn now() -> usize {
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as usize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use serde_json::Value;
+
+    const KEY: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/AuthKey_TEST.p8"
+    );
+    const ISSUER_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+    fn auth(key_id: Option<&str>, issuer_id: Option<&str>, key_type: KeyType) -> AppStoreAuth {
+        return AppStoreAuth::new(
+            KeySource::new(KEY.to_string()),
+            key_id.map(str::to_string),
+            issuer_id.map(str::to_string),
+            key_type,
+        );
+    }
+
+    /// The header and claims of a token, without checking its signature.
+    fn decode(token: &str) -> (Value, Value) {
+        let parts: Vec<Value> = token
+            .split('.')
+            .take(2)
+            .map(|part| serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap())
+            .collect();
+
+        return (parts[0].clone(), parts[1].clone());
+    }
+
+    #[test]
+    fn team_key_tokens_have_an_issuer() {
+        let token = auth(None, Some(ISSUER_ID), KeyType::Team).token().unwrap();
+        let (header, claims) = decode(&token);
+
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["kid"], "TEST");
+        assert_eq!(claims["iss"], ISSUER_ID);
+        assert_eq!(claims.get("sub"), None);
+        assert_eq!(claims["aud"], "appstoreconnect-v1");
+        assert_eq!(
+            claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap(),
+            TOKEN_LIFETIME_SECONDS as u64
+        );
+    }
+
+    #[test]
+    fn individual_key_tokens_have_a_user_subject() {
+        let token = auth(None, None, KeyType::Individual).token().unwrap();
+        let (_, claims) = decode(&token);
+
+        assert_eq!(claims.get("iss"), None);
+        assert_eq!(claims["sub"], "user");
+    }
+
+    #[test]
+    fn issuer_must_match_the_key_type() {
+        assert!(auth(None, None, KeyType::Team).token().is_err());
+        assert!(auth(None, Some(ISSUER_ID), KeyType::Individual)
+            .token()
+            .is_err());
+    }
+
+    #[test]
+    fn key_id_defaults_to_the_key_file_name() {
+        assert_eq!(
+            auth(None, None, KeyType::Team).key_id(),
+            Ok("TEST".to_string())
+        );
+        assert_eq!(
+            auth(Some("GIVEN"), None, KeyType::Team).key_id(),
+            Ok("GIVEN".to_string())
+        );
+    }
+
+    #[test]
+    fn key_id_is_required_for_other_file_names() {
+        for source in [
+            "/keys/key.p8",
+            "/keys/AuthKey_.p8",
+            "/keys/AuthKey_X.pem",
+            "env:KEY",
+        ] {
+            let auth = AppStoreAuth::new(
+                KeySource::new(source.to_string()),
+                None,
+                None,
+                KeyType::Team,
+            );
+
+            assert!(auth.key_id().is_err(), "{}", source);
+        }
+    }
+}
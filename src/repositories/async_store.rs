@@ -0,0 +1,437 @@
+use super::store::{Review, ReviewFilter};
+#[cfg(feature = "ios")]
+use super::{
+    app_store_auth::AppStoreAuth,
+    key_source::KeySource,
+    store::{app_store_reviews, localization_id, version_id, AppStoreSettings},
+};
+#[cfg(feature = "android")]
+use super::{
+    google_auth::{GoogleAuth, ANDROID_PUBLISHER_SCOPE},
+    store::{
+        changelog_track, check_review_filter, draft_track, google_play_reviews, GooglePlaySettings,
+    },
+    token_cache::TokenCache,
+};
+#[cfg(feature = "android")]
+use crate::data::async_google_play_datasource::AsyncGooglePlayDataSource;
+use crate::data::http;
+#[cfg(feature = "ios")]
+use crate::data::{
+    app_store_datasource::CustomerReviewsResponse,
+    async_app_store_datasource::AsyncAppStoreDataSource,
+};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+/// The async counterpart of `Store`. The operations take `&self`, so that one
+/// store can update several locales at once, and their futures are `Send`, so
+/// that they can be spawned on a multi-threaded runtime.
+pub trait AsyncStore {
+    fn set_changelog(
+        &self,
+        locale: &str,
+        version: &str,
+        changelog: &str,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+
+    fn create_version(&self, version: &str) -> impl Future<Output = Result<(), String>> + Send;
+
+    /// Lists the customer reviews matching the filter, newest first.
+    fn reviews(
+        &self,
+        filter: &ReviewFilter,
+    ) -> impl Future<Output = Result<Vec<Review>, String>> + Send;
+
+    fn reply_to_review(
+        &self,
+        review_id: &str,
+        reply: &str,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// The async counterpart of `AppStore`.
+#[cfg(feature = "ios")]
+pub struct AsyncAppStore {
+    auth: Arc<Mutex<AppStoreAuth>>,
+    app_id: String,
+}
+
+#[cfg(feature = "ios")]
+impl AsyncAppStore {
+    /// Fails when the key path or the app ID is missing, see `AppStore::new`.
+    pub fn new(settings: AppStoreSettings) -> Result<Self, String> {
+        let key_path = settings.key_path.ok_or("Key path is required")?;
+        let app_id = settings.app_id.ok_or("App ID is required")?;
+
+        return Ok(Self {
+            app_id,
+            auth: Arc::new(Mutex::new(AppStoreAuth::new(
+                KeySource::new(key_path),
+                settings.key_id,
+                settings.issuer_id,
+                settings.key_type,
+            ))),
+        });
+    }
+
+    /// A data source with a valid token, a new one when `renew` is set. Reading
+    /// the key blocks, on a file or stdin, so it runs on tokio's blocking threads.
+    async fn data_source(&self, renew: bool) -> Result<AsyncAppStoreDataSource, String> {
+        let auth = self.auth.clone();
+
+        return tokio::task::spawn_blocking(move || {
+            let mut auth = auth.lock().map_err(|e| e.to_string())?;
+
+            if renew {
+                auth.revoke();
+            }
+
+            return auth.token().map(AsyncAppStoreDataSource::new);
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    /// Calls the API with a data source, and once more with a new token if the
+    /// API rejected the token on its first request, see `AppStoreAuth::with_token`.
+    async fn with_data_source<T, F>(
+        &self,
+        call: impl Fn(AsyncAppStoreDataSource) -> F,
+    ) -> Result<T, String>
+    where
+        F: Future<Output = Result<T, String>>,
+    {
+        let data_source = self.data_source(false).await?;
+        let token = data_source.token().to_string();
+
+        match call(data_source).await {
+            Err(_) if http::is_rejected_unused(&token) => {
+                return call(self.data_source(true).await?).await
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(feature = "ios")]
+impl AsyncStore for AsyncAppStore {
+    async fn set_changelog(
+        &self,
+        locale: &str,
+        version: &str,
+        changelog: &str,
+    ) -> Result<(), String> {
+        return self
+            .with_data_source(move |data_source| async move {
+                let response = data_source
+                    .get_app_store_version(&self.app_id, version)
+                    .await?;
+                let version_id = version_id(&response)?;
+                let localizations = data_source
+                    .get_app_version_localizations(&version_id)
+                    .await?;
+                let localization_id = localization_id(&localizations, locale)?;
+
+                return data_source
+                    .patch_whats_new(&localization_id, changelog)
+                    .await;
+            })
+            .await;
+    }
+
+    async fn create_version(&self, version: &str) -> Result<(), String> {
+        return self
+            .with_data_source(move |data_source| async move {
+                data_source.create_version(&self.app_id, version).await
+            })
+            .await;
+    }
+
+    async fn reviews(&self, filter: &ReviewFilter) -> Result<Vec<Review>, String> {
+        return self
+            .with_data_source(move |data_source| async move {
+                let mut response = data_source
+                    .get_customer_reviews(
+                        &self.app_id,
+                        &filter.ratings,
+                        filter.territory.as_deref(),
+                    )
+                    .await?;
+                let mut reviews: Vec<Review> = vec![];
+
+                loop {
+                    reviews.extend(app_store_reviews(&response)?);
+
+                    // Reviews are sorted by date, so there is no point going past `since`.
+                    let is_past_since = filter
+                        .since
+                        .zip(reviews.last())
+                        .is_some_and(|(since, review)| review.created_at < since);
+
+                    match response.links.next.as_deref() {
+                        Some(next) if !is_past_since => {
+                            response = data_source
+                                .get_next_page::<CustomerReviewsResponse>(next)
+                                .await?;
+                        }
+                        _ => break,
+                    }
+                }
+
+                reviews.retain(|review| filter.matches(review));
+
+                return Ok(reviews);
+            })
+            .await;
+    }
+
+    async fn reply_to_review(&self, review_id: &str, reply: &str) -> Result<(), String> {
+        return self
+            .with_data_source(move |data_source| async move {
+                data_source
+                    .create_customer_review_response(review_id, reply)
+                    .await
+            })
+            .await;
+    }
+}
+
+/// The async counterpart of `GooglePlay`.
+#[cfg(feature = "android")]
+pub struct AsyncGooglePlay {
+    pub track: String,
+    auth: Arc<Mutex<GoogleAuth>>,
+    package_name: String,
+}
+
+#[cfg(feature = "android")]
+impl AsyncGooglePlay {
+    /// Fails when the package name or the track is missing, or when no
+    /// credentials are found, see `GooglePlay::new`.
+    pub fn new(settings: GooglePlaySettings) -> Result<Self, String> {
+        let key = GoogleAuth::credentials_source(settings.key_path)?;
+        let package_name = settings.package_name.ok_or("Package name is required")?;
+        let track = settings.track.ok_or("Track is required")?;
+
+        return Ok(Self {
+            track,
+            package_name,
+            auth: Arc::new(Mutex::new(GoogleAuth::new(
+                key,
+                settings.token_cache.map(TokenCache::new),
+            ))),
+        });
+    }
+
+    /// A valid access token, a new one when `renew` is set. Exchanging tokens
+    /// goes through the blocking client, on tokio's blocking threads.
+    async fn token(&self, renew: bool) -> Result<String, String> {
+        let auth = self.auth.clone();
+
+        return tokio::task::spawn_blocking(move || {
+            let mut auth = auth.lock().map_err(|e| e.to_string())?;
+
+            if renew {
+                auth.revoke(&[ANDROID_PUBLISHER_SCOPE])?;
+            }
+
+            return auth.token_for(&[ANDROID_PUBLISHER_SCOPE]);
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    /// Calls the API with a token, and once more with a new one if the API
+    /// rejected the token on its first request, see `GoogleAuth::with_token`.
+    async fn with_token<T, F>(&self, call: impl Fn(String) -> F) -> Result<T, String>
+    where
+        F: Future<Output = Result<T, String>>,
+    {
+        let token = self.token(false).await?;
+
+        match call(token.clone()).await {
+            Err(_) if http::is_rejected_unused(&token) => {
+                return call(self.token(true).await?).await
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(feature = "android")]
+impl AsyncStore for AsyncGooglePlay {
+    async fn set_changelog(
+        &self,
+        locale: &str,
+        version: &str,
+        changelog: &str,
+    ) -> Result<(), String> {
+        return self
+            .with_token(move |token| async move {
+                let edit_id =
+                    AsyncGooglePlayDataSource::create_edit_session(&token, &self.package_name)
+                        .await?;
+                let track = AsyncGooglePlayDataSource::get_track(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                    &self.track,
+                )
+                .await?;
+                let track = changelog_track(&track, version, locale, changelog)?;
+
+                AsyncGooglePlayDataSource::update_track(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                    &track,
+                )
+                .await?;
+
+                return AsyncGooglePlayDataSource::commit_edits(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                )
+                .await;
+            })
+            .await;
+    }
+
+    async fn create_version(&self, version: &str) -> Result<(), String> {
+        return self
+            .with_token(move |token| async move {
+                let edit_id =
+                    AsyncGooglePlayDataSource::create_edit_session(&token, &self.package_name)
+                        .await?;
+                let track = AsyncGooglePlayDataSource::get_track(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                    &self.track,
+                )
+                .await?;
+                let track = draft_track(&track, version)?;
+
+                AsyncGooglePlayDataSource::update_track(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                    &track,
+                )
+                .await?;
+
+                return AsyncGooglePlayDataSource::commit_edits(
+                    &token,
+                    &self.package_name,
+                    &edit_id,
+                )
+                .await;
+            })
+            .await;
+    }
+
+    async fn reviews(&self, filter: &ReviewFilter) -> Result<Vec<Review>, String> {
+        check_review_filter(filter)?;
+
+        return self
+            .with_token(move |token| async move {
+                let mut page_token: Option<String> = None;
+                let mut reviews: Vec<Review> = vec![];
+
+                loop {
+                    let response = AsyncGooglePlayDataSource::get_reviews(
+                        &token,
+                        &self.package_name,
+                        page_token.as_deref(),
+                    )
+                    .await?;
+
+                    reviews.extend(google_play_reviews(response.reviews)?);
+                    page_token = response.token_pagination.and_then(|p| p.next_page_token);
+
+                    if page_token.is_none() {
+                        break;
+                    }
+                }
+
+                reviews.retain(|review| filter.matches(review));
+                reviews.sort_by_key(|review| std::cmp::Reverse(review.created_at));
+
+                return Ok(reviews);
+            })
+            .await;
+    }
+
+    async fn reply_to_review(&self, review_id: &str, reply: &str) -> Result<(), String> {
+        return self
+            .with_token(move |token| async move {
+                AsyncGooglePlayDataSource::reply_to_review(
+                    &token,
+                    &self.package_name,
+                    review_id,
+                    reply,
+                )
+                .await
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "ios")]
+    const KEY: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/AuthKey_TEST.p8"
+    );
+    #[cfg(feature = "android")]
+    const SERVICE_ACCOUNT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/service_account.json"
+    );
+
+    /// Only compiles when the future can be spawned on a multi-threaded runtime.
+    /// The futures are never polled, so nothing is sent.
+    fn assert_send<T: Send>(_: T) {}
+
+    #[cfg(feature = "ios")]
+    #[test]
+    fn app_store_futures_are_send() {
+        let store = AsyncAppStore::new(AppStoreSettings {
+            key_path: Some(KEY.to_string()),
+            app_id: Some("1".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let filter = ReviewFilter::default();
+
+        assert_send(store.set_changelog("en-US", "1.0.0", "Notes"));
+        assert_send(store.create_version("1.0.0"));
+        assert_send(store.reviews(&filter));
+        assert_send(store.reply_to_review("1", "Thanks"));
+    }
+
+    #[cfg(feature = "android")]
+    #[test]
+    fn google_play_futures_are_send() {
+        let store = AsyncGooglePlay::new(GooglePlaySettings {
+            key_path: Some(SERVICE_ACCOUNT.to_string()),
+            package_name: Some("com.example.app".to_string()),
+            track: Some("beta".to_string()),
+            token_cache: None,
+        })
+        .unwrap();
+        let filter = ReviewFilter::default();
+
+        assert_send(store.set_changelog("en-US", "1.0.0", "Notes"));
+        assert_send(store.create_version("1.0.0"));
+        assert_send(store.reviews(&filter));
+        assert_send(store.reply_to_review("1", "Thanks"));
+    }
+}
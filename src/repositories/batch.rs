@@ -0,0 +1,582 @@
+use super::store::Store;
+#[cfg(feature = "ios")]
+use super::store::{AppStore, AppStoreSettings};
+#[cfg(feature = "android")]
+use super::store::{GooglePlay, GooglePlaySettings};
+use crate::config::{Config, KeyType, Profile};
+#[cfg(feature = "android")]
+use crate::data::google_play_datasource::ReleaseStatus;
+use crate::data::logging;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const STDIN: &str = "-";
+const STDIN_ERROR: &str = "Keys can't be read from stdin in a batch, use a file or env:NAME";
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    /// Creates the version, a draft release on Google Play.
+    CreateVersion,
+    /// Sets the release notes of the version, one per locale.
+    Notes,
+    /// Copies the release from `from_track` to the track of the app, Google Play only.
+    Promote,
+    /// Changes the rollout percentage of the release, replacing the release in
+    /// progress, Google Play only.
+    Rollout,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Android,
+    Ios,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Android => return "android",
+            Platform::Ios => return "ios",
+        }
+    }
+}
+
+/// An app of the manifest. The values missing from the app are taken from its
+/// configuration profile.
+#[derive(Debug, Deserialize)]
+pub struct BatchApp {
+    /// Shown in the summary, defaults to the package name or app ID.
+    pub name: Option<String>,
+    pub platform: Platform,
+    /// Defaults to the `--profile` of the command line.
+    pub profile: Option<String>,
+    /// A key of its own, the key ID, issuer ID and key type of the profile
+    /// being ignored then.
+    pub key_path: Option<String>,
+    pub key_id: Option<String>,
+    pub issuer_id: Option<String>,
+
+    #[serde(default)]
+    pub key_type: KeyType,
+
+    pub app_id: Option<String>,
+    pub package_name: Option<String>,
+    pub track: Option<String>,
+}
+
+/// The `freitool batch` manifest, running the same operation on many apps.
+///
+/// ```yaml
+/// operation: rollout   # create-version, notes, promote or rollout
+/// version: 1.2.0
+/// percent: 20          # rollout and promote, 100 completes the release
+/// from_track: beta     # promote
+/// notes:               # notes
+///   en-US: Bug fixes
+/// concurrency: 4
+/// apps:
+///   - name: acme
+///     platform: android
+///     profile: acme
+///     package_name: com.acme.app
+///     track: production
+///   - platform: ios
+///     app_id: "1234567890"
+///     key_path: /path/to/AuthKey_XXXX.p8  # with its own issuer_id or key_type
+///     issuer_id: 00000000-0000-0000-0000-000000000000
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Batch {
+    pub operation: Operation,
+    pub version: String,
+
+    #[serde(default)]
+    pub notes: BTreeMap<String, String>,
+
+    pub from_track: Option<String>,
+    pub percent: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub apps: Vec<BatchApp>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AppStatus {
+    Succeeded,
+    Failed,
+}
+
+/// The outcome of the operation on one app.
+#[derive(Debug, Serialize)]
+pub struct AppOutcome {
+    pub app: String,
+    pub platform: Platform,
+    pub status: AppStatus,
+    pub duration_ms: u128,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The report of a batch, with the apps in the order of the manifest.
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    pub operation: Operation,
+    pub version: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub apps: Vec<AppOutcome>,
+}
+
+impl BatchReport {
+    /// 0 when every app succeeded, 1 when all failed, and 2 when some failed.
+    pub fn exit_code(&self) -> i32 {
+        match (self.succeeded, self.failed) {
+            (_, 0) => return 0,
+            (0, _) => return 1,
+            _ => return 2,
+        }
+    }
+}
+
+impl Batch {
+    /// Loads and validates the manifest at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read manifest {}: {}", path, e))?;
+        let batch: Batch = serde_yaml::from_str(&content)
+            .map_err(|e| format!("Failed to parse manifest {}: {}", path, e))?;
+
+        batch.validate()?;
+
+        return Ok(batch);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.apps.is_empty() {
+            return Err("The manifest has no apps".to_string());
+        }
+
+        if self.concurrency == Some(0) {
+            return Err("The concurrency must be at least 1".to_string());
+        }
+
+        if self.percent.is_some_and(|p| p <= 0.0 || p > 100.0) {
+            return Err("The percent must be between 0 and 100".to_string());
+        }
+
+        if self
+            .apps
+            .iter()
+            .any(|app| app.key_path.as_deref() == Some(STDIN))
+        {
+            return Err(STDIN_ERROR.to_string());
+        }
+
+        match self.operation {
+            Operation::Notes if self.notes.is_empty() => {
+                return Err("The notes operation requires notes".to_string());
+            }
+            Operation::Promote if self.from_track.is_none() => {
+                return Err("The promote operation requires from_track".to_string());
+            }
+            Operation::Rollout if self.percent.is_none() => {
+                return Err("The rollout operation requires percent".to_string());
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    /// Fails when an app would read the key of its profile from stdin, which
+    /// can only be read once, while the apps run concurrently.
+    pub fn check_keys(&self, config: &Config, profile: Option<&str>) -> Result<(), String> {
+        for app in self.apps.iter().filter(|app| app.key_path.is_none()) {
+            let Ok(profile) = config.profile(app.profile.as_deref().or(profile)) else {
+                continue;
+            };
+
+            let key_path = match app.platform {
+                Platform::Android => profile.android.key_path,
+                Platform::Ios => profile.ios.key_path,
+            };
+
+            if key_path.as_deref() == Some(STDIN) {
+                return Err(STDIN_ERROR.to_string());
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Runs the operation on every app, `concurrency` apps at a time. A failing
+    /// app does not stop the others, its error is in the report.
+    pub fn run(
+        &self,
+        config: &Config,
+        profile: Option<&str>,
+        concurrency: Option<usize>,
+    ) -> BatchReport {
+        let concurrency = concurrency
+            .or(self.concurrency)
+            .unwrap_or(DEFAULT_CONCURRENCY)
+            .clamp(1, self.apps.len());
+        let next = AtomicUsize::new(0);
+        let outcomes: Mutex<Vec<Option<AppOutcome>>> =
+            Mutex::new(self.apps.iter().map(|_| None).collect());
+
+        thread::scope(|scope| {
+            for _ in 0..concurrency {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(app) = self.apps.get(index) else {
+                        break;
+                    };
+
+                    let outcome = self.run_app(index, app, config, profile);
+                    outcomes.lock().unwrap()[index] = Some(outcome);
+                });
+            }
+        });
+
+        let apps: Vec<AppOutcome> = outcomes
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        let failed = apps
+            .iter()
+            .filter(|a| a.status == AppStatus::Failed)
+            .count();
+
+        return BatchReport {
+            operation: self.operation,
+            version: self.version.clone(),
+            succeeded: apps.len() - failed,
+            failed,
+            apps,
+        };
+    }
+
+    fn run_app(
+        &self,
+        index: usize,
+        app: &BatchApp,
+        config: &Config,
+        profile: Option<&str>,
+    ) -> AppOutcome {
+        let start = Instant::now();
+        let profile = config.profile(app.profile.as_deref().or(profile));
+        let name = app_name(index, app, profile.as_ref().ok());
+
+        let result = profile.and_then(|profile| match app.platform {
+            Platform::Android => self.run_android(app, &profile),
+            Platform::Ios => self.run_ios(app, &profile),
+        });
+
+        let (status, error) = match result {
+            Ok(()) => (AppStatus::Succeeded, None),
+            Err(e) => (AppStatus::Failed, Some(e)),
+        };
+
+        let message = match &error {
+            Some(e) => format!("{} failed: {}", name, e),
+            None => format!("{} succeeded", name),
+        };
+        logging::log(1, "batch", &message, json!({ "app": name, "error": error }));
+
+        return AppOutcome {
+            app: name,
+            platform: app.platform,
+            status,
+            duration_ms: start.elapsed().as_millis(),
+            error,
+        };
+    }
+
+    #[cfg(feature = "android")]
+    fn run_android(&self, app: &BatchApp, profile: &Profile) -> Result<(), String> {
+        let mut store = GooglePlay::new(GooglePlaySettings {
+            key_path: app.key_path.clone().or(profile.android.key_path.clone()),
+            package_name: app
+                .package_name
+                .clone()
+                .or(profile.android.package_name.clone()),
+            track: app.track.clone().or(profile.android.track.clone()),
+            token_cache: profile
+                .android
+                .token_cache
+                .clone()
+                .or_else(|| std::env::var("FREITOOL_TOKEN_CACHE").ok()),
+        })?;
+
+        match self.operation {
+            Operation::Promote => {
+                let from_track = self.from_track.as_deref().unwrap_or_default();
+                return store.promote(from_track, &self.version, self.user_fraction());
+            }
+
+            Operation::Rollout => {
+                let status = match self.user_fraction() {
+                    Some(_) => ReleaseStatus::InProgress,
+                    None => ReleaseStatus::Completed,
+                };
+                return store.set_rollout(&self.version, status, self.user_fraction());
+            }
+
+            _ => return self.run_store(&mut store),
+        }
+    }
+
+    #[cfg(not(feature = "android"))]
+    fn run_android(&self, _app: &BatchApp, _profile: &Profile) -> Result<(), String> {
+        return Err("Built without the android feature".to_string());
+    }
+
+    #[cfg(feature = "ios")]
+    fn run_ios(&self, app: &BatchApp, profile: &Profile) -> Result<(), String> {
+        let (key_path, key_id, issuer_id, key_type) = match &app.key_path {
+            Some(key_path) => (
+                key_path.clone(),
+                app.key_id.clone(),
+                app.issuer_id.clone(),
+                app.key_type,
+            ),
+            None => (
+                profile.ios.key_path.clone().ok_or("Key path is required")?,
+                app.key_id.clone().or(profile.ios.key_id.clone()),
+                app.issuer_id.clone().or(profile.ios.issuer_id.clone()),
+                profile.ios.key_type,
+            ),
+        };
+
+        let mut store = AppStore::new(AppStoreSettings {
+            key_path: Some(key_path),
+            key_id,
+            issuer_id,
+            key_type,
+            app_id: app.app_id.clone().or(profile.ios.app_id.clone()),
+            vendor_number: None,
+        })?;
+
+        match self.operation {
+            Operation::Promote | Operation::Rollout => {
+                return Err("Promotions and rollouts are only supported on Google Play".to_string());
+            }
+
+            _ => return self.run_store(&mut store),
+        }
+    }
+
+    #[cfg(not(feature = "ios"))]
+    fn run_ios(&self, _app: &BatchApp, _profile: &Profile) -> Result<(), String> {
+        return Err("Built without the ios feature".to_string());
+    }
+
+    /// The operations common to both stores.
+    fn run_store(&self, store: &mut dyn Store) -> Result<(), String> {
+        match self.operation {
+            Operation::CreateVersion => return store.create_version(&self.version),
+
+            Operation::Notes => {
+                for (locale, notes) in &self.notes {
+                    store
+                        .set_changelog(locale, &self.version, notes)
+                        .map_err(|e| format!("{}: {}", locale, e))?;
+                }
+
+                return Ok(());
+            }
+
+            Operation::Promote | Operation::Rollout => {
+                unreachable!("Store specific operation");
+            }
+        }
+    }
+
+    /// The user fraction of `percent`, none for a complete release.
+    #[cfg(feature = "android")]
+    fn user_fraction(&self) -> Option<f64> {
+        return self.percent.filter(|p| *p < 100.0).map(|p| p / 100.0);
+    }
+}
+
+/// The name of the app in the report: its `name`, its package name or app ID,
+/// or its position in the manifest.
+fn app_name(index: usize, app: &BatchApp, profile: Option<&Profile>) -> String {
+    let id = match app.platform {
+        Platform::Android => app
+            .package_name
+            .clone()
+            .or(profile.and_then(|p| p.android.package_name.clone())),
+        Platform::Ios => app
+            .app_id
+            .clone()
+            .or(profile.and_then(|p| p.ios.app_id.clone())),
+    };
+
+    return app.name.clone().or(id).unwrap_or(format!("#{}", index + 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APPS: &str = "apps: [{ platform: android, package_name: com.example.app }]";
+
+    fn validate(manifest: &str) -> Result<(), String> {
+        let batch: Batch = serde_yaml::from_str(manifest).unwrap();
+        return batch.validate();
+    }
+
+    #[test]
+    fn validate_accepts_complete_manifests() {
+        for fields in [
+            "operation: create-version\nversion: 1.0.0",
+            "operation: notes\nversion: 1.0.0\nnotes: { en-US: Fixes }",
+            "operation: promote\nversion: 1.0.0\nfrom_track: beta",
+            "operation: rollout\nversion: 1.0.0\npercent: 100\nconcurrency: 1",
+        ] {
+            let manifest = format!("{}\n{}", fields, APPS);
+
+            assert_eq!(validate(&manifest), Ok(()), "{}", manifest);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_percents_out_of_range() {
+        for percent in ["0", "-5", "100.5", "150"] {
+            let manifest = format!(
+                "operation: rollout\nversion: 1.0.0\npercent: {}\n{}",
+                percent, APPS
+            );
+
+            assert_eq!(
+                validate(&manifest),
+                Err("The percent must be between 0 and 100".to_string()),
+                "{}",
+                percent
+            );
+        }
+    }
+
+    #[test]
+    fn validate_rejects_a_zero_concurrency() {
+        let manifest = format!(
+            "operation: create-version\nversion: 1.0.0\nconcurrency: 0\n{}",
+            APPS
+        );
+
+        assert_eq!(
+            validate(&manifest),
+            Err("The concurrency must be at least 1".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_requires_the_operation_fields() {
+        let cases = [
+            ("notes", "The notes operation requires notes"),
+            ("promote", "The promote operation requires from_track"),
+            ("rollout", "The rollout operation requires percent"),
+        ];
+
+        for (operation, error) in cases {
+            let manifest = format!("operation: {}\nversion: 1.0.0\n{}", operation, APPS);
+
+            assert_eq!(validate(&manifest), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn validate_rejects_empty_manifests_and_stdin_keys() {
+        assert!(validate("operation: create-version\nversion: 1.0.0\napps: []").is_err());
+
+        let manifest = "operation: create-version\nversion: 1.0.0\n\
+                        apps: [{ platform: ios, app_id: '1', key_path: '-' }]";
+        assert_eq!(validate(manifest), Err(STDIN_ERROR.to_string()));
+    }
+
+    #[test]
+    fn check_keys_rejects_profiles_reading_stdin() {
+        let config: Config = serde_yaml::from_str(
+            "profiles:\n  stdin: { ios: { key_path: '-' } }\n  file: { ios: { key_path: key.p8 } }",
+        )
+        .unwrap();
+        let batch = |apps: &str| -> Batch {
+            serde_yaml::from_str(&format!(
+                "operation: create-version\nversion: 1.0.0\napps: {}",
+                apps
+            ))
+            .unwrap()
+        };
+
+        let uses_stdin = batch("[{ platform: ios, profile: file }, { platform: ios }]");
+        assert_eq!(
+            uses_stdin.check_keys(&config, Some("stdin")),
+            Err(STDIN_ERROR.to_string())
+        );
+        assert_eq!(uses_stdin.check_keys(&config, Some("file")), Ok(()));
+
+        // Its own key, or the key of the other platform, is not read from stdin.
+        let own_key = batch("[{ platform: ios, key_path: key.p8 }, { platform: android }]");
+        assert_eq!(own_key.check_keys(&config, Some("stdin")), Ok(()));
+    }
+
+    fn report(succeeded: usize, failed: usize) -> BatchReport {
+        return BatchReport {
+            operation: Operation::CreateVersion,
+            version: "1.0.0".to_string(),
+            succeeded,
+            failed,
+            apps: vec![],
+        };
+    }
+
+    #[test]
+    fn exit_code_tells_partial_failures_apart() {
+        assert_eq!(report(3, 0).exit_code(), 0);
+        assert_eq!(report(0, 3).exit_code(), 1);
+        assert_eq!(report(2, 1).exit_code(), 2);
+    }
+
+    #[test]
+    fn run_reports_the_apps_in_the_manifest_order() {
+        // The apps fail before reaching the network, without package name or
+        // app ID, each one with its own name.
+        let apps: Vec<String> = (1..=9)
+            .map(|i| match i % 2 {
+                0 => format!("{{ name: app{}, platform: android, track: beta }}", i),
+                _ => format!("{{ name: app{}, platform: ios, key_path: key.p8 }}", i),
+            })
+            .collect();
+        let batch: Batch = serde_yaml::from_str(&format!(
+            "operation: create-version\nversion: 1.0.0\napps: [{}]",
+            apps.join(", ")
+        ))
+        .unwrap();
+
+        let report = batch.run(&Config::default(), None, Some(4));
+
+        let names: Vec<&str> = report.apps.iter().map(|a| a.app.as_str()).collect();
+        assert_eq!(
+            names,
+            ["app1", "app2", "app3", "app4", "app5", "app6", "app7", "app8", "app9"]
+        );
+        assert_eq!((report.succeeded, report.failed), (0, 9));
+        assert_eq!(report.exit_code(), 1);
+    }
+}
@@ -0,0 +1,356 @@
+#[cfg(feature = "android")]
+use super::google_auth::{GoogleAuth, ANDROID_PUBLISHER_SCOPE, PLAY_REPORTING_SCOPE};
+#[cfg(feature = "ios")]
+use super::{app_store_auth::AppStoreAuth, key_source::KeySource};
+#[cfg(feature = "ios")]
+use crate::config::IosProfile;
+#[cfg(feature = "android")]
+use crate::{
+    config::AndroidProfile,
+    data::{
+        google_play_datasource::GooglePlayDataSource,
+        play_reporting_datasource::PlayReportingDataSource,
+    },
+};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// A line of the `doctor` checklist.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub profile: String,
+    pub platform: &'static str,
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<&'static str>,
+}
+
+struct Checklist {
+    profile: String,
+    platform: &'static str,
+    checks: Vec<Check>,
+}
+
+impl Checklist {
+    fn new(profile: &str, platform: &'static str) -> Self {
+        return Self {
+            profile: profile.to_string(),
+            platform,
+            checks: vec![],
+        };
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        status: CheckStatus,
+        detail: String,
+        fix: Option<&'static str>,
+    ) {
+        self.checks.push(Check {
+            profile: self.profile.clone(),
+            platform: self.platform,
+            name,
+            status,
+            detail,
+            fix,
+        });
+    }
+
+    /// Records the result of a check, returning whether it passed.
+    fn check(
+        &mut self,
+        name: &'static str,
+        fix: &'static str,
+        result: Result<String, String>,
+    ) -> bool {
+        match result {
+            Ok(detail) => self.push(name, CheckStatus::Passed, detail, None),
+            Err(e) => self.push(name, CheckStatus::Failed, e, Some(fix)),
+        }
+
+        return self
+            .checks
+            .last()
+            .is_some_and(|c| c.status == CheckStatus::Passed);
+    }
+
+    fn skip(&mut self, name: &'static str, detail: &str) {
+        self.push(name, CheckStatus::Skipped, detail.to_string(), None);
+    }
+}
+
+/// Fails when a check failed, or when every check was skipped as no profile is
+/// configured.
+pub fn status(checks: &[Check]) -> Result<(), String> {
+    if checks.iter().any(|c| c.status == CheckStatus::Failed) {
+        return Err("Some checks failed".to_string());
+    }
+
+    if checks.iter().all(|c| c.status == CheckStatus::Skipped) {
+        return Err("Nothing to check, configure a profile in freitool.yaml".to_string());
+    }
+
+    return Ok(());
+}
+
+/// Checks the App Store Connect key, then that the app can be reached with the
+/// permissions the commands need. The checks stop at the first failure of the
+/// credentials, as the next ones would fail the same way.
+#[cfg(feature = "ios")]
+pub fn check_ios(profile_name: &str, profile: &IosProfile) -> Vec<Check> {
+    let mut list = Checklist::new(profile_name, "ios");
+
+    let Some(key_path) = profile.key_path.clone() else {
+        list.skip("Configuration", "No key_path, iOS is not configured");
+        return list.checks;
+    };
+
+    let mut auth = AppStoreAuth::new(
+        KeySource::new(key_path),
+        profile.key_id.clone(),
+        profile.issuer_id.clone(),
+        profile.key_type,
+    );
+
+    let is_valid = list.check(
+        "Key",
+        "Download the .p8 key again from App Store Connect > Users and Access > Integrations",
+        auth.encoding_key()
+            .map(|_| format!("{} is a valid .p8 key", auth.key)),
+    ) && list.check(
+        "Key ID",
+        "Set key_id or --key-id, or name the key file AuthKey_{ID}.p8",
+        auth.key_id(),
+    ) && list.check(
+        "Issuer ID",
+        "Copy the issuer ID from App Store Connect > Users and Access > Integrations, or set key_type: individual",
+        match (auth.issuer(), &profile.issuer_id) {
+            (Err(e), _) => Err(e),
+            (Ok(_), Some(issuer_id)) if is_uuid(issuer_id) => Ok(issuer_id.clone()),
+            (Ok(_), Some(issuer_id)) => Err(format!("{} is not a UUID", issuer_id)),
+            (Ok(_), None) => Ok("None, using an individual key".to_string()),
+        },
+    );
+
+    if !is_valid {
+        return list.checks;
+    }
+
+    let Some(app_id) = profile.app_id.clone() else {
+        list.check(
+            "App",
+            "Set app_id or --app-id",
+            Err("No app_id".to_string()),
+        );
+        return list.checks;
+    };
+
+    let has_app = list.check(
+        "App",
+        "Check that the key is active and matches the issuer ID, and that the app ID is right",
+        auth.with_data_source(|ds| ds.get_app(&app_id)).map(|app| {
+            format!(
+                "{} ({})",
+                app.data.attributes.name, app.data.attributes.bundle_id
+            )
+        }),
+    );
+
+    if !has_app {
+        return list.checks;
+    }
+
+    list.check(
+        "TestFlight",
+        "Give the key the App Manager role",
+        auth.with_data_source(|ds| ds.get_beta_groups(&app_id))
+            .map(|groups| format!("{} beta groups", groups.len())),
+    );
+
+    list.check(
+        "Customer reviews",
+        "Give the key the App Manager or Customer Support role",
+        auth.with_data_source(|ds| ds.get_customer_reviews(&app_id, &[], None))
+            .map(|_| "Readable".to_string()),
+    );
+
+    list.check(
+        "Analytics",
+        "Give the key the Admin role",
+        auth.with_data_source(|ds| ds.get_analytics_report_requests(&app_id))
+            .map(|requests| format!("{} report requests", requests.len())),
+    );
+
+    match &profile.vendor_number {
+        Some(vendor_number) => list.push(
+            "Vendor number",
+            CheckStatus::Passed,
+            vendor_number.clone(),
+            None,
+        ),
+        None => list.skip("Vendor number", "Not set, required by the reports commands"),
+    }
+
+    return list.checks;
+}
+
+/// Checks the Google credentials, then that the package can be reached with the
+/// permissions the commands need.
+#[cfg(feature = "android")]
+pub fn check_android(profile_name: &str, profile: &AndroidProfile) -> Vec<Check> {
+    let mut list = Checklist::new(profile_name, "android");
+
+    let Some(package_name) = profile.package_name.clone() else {
+        list.skip(
+            "Configuration",
+            "No package_name, Android is not configured",
+        );
+        return list.checks;
+    };
+
+    let key = match GoogleAuth::credentials_source(profile.key_path.clone()) {
+        Ok(key) => key,
+        Err(e) => {
+            list.check(
+                "Credentials",
+                "Set key_path or GOOGLE_APPLICATION_CREDENTIALS",
+                Err(e),
+            );
+            return list.checks;
+        }
+    };
+
+    let mut auth = GoogleAuth::new(key, None);
+
+    let is_valid = list.check(
+        "Credentials",
+        "Download a new JSON key of the service account from the Google Cloud console",
+        auth.account(),
+    ) && list.check(
+        "Access token",
+        "Check that the key is not revoked and the Google Play Android Developer API is enabled",
+        auth.token_for(&[ANDROID_PUBLISHER_SCOPE])
+            .map(|_| "Exchanged".to_string()),
+    );
+
+    if !is_valid {
+        return list.checks;
+    }
+
+    let fix = "Check the package name, and invite the service account in Play Console > Users and permissions";
+    let edit_id = match auth
+        .with_token(|token| GooglePlayDataSource::create_edit_session(token, &package_name))
+    {
+        Ok(edit_id) => edit_id,
+        Err(e) => {
+            list.check("App", fix, Err(e));
+            return list.checks;
+        }
+    };
+
+    list.check("App", fix, Ok(package_name.clone()));
+
+    let track = profile.track.clone().unwrap_or("production".to_string());
+
+    list.check(
+        "Releases",
+        "Grant the release permissions to the service account in Play Console",
+        auth.with_token(|token| {
+            GooglePlayDataSource::get_track(token, &package_name, &edit_id, &track)
+        })
+        .map(|track| format!("{} releases on {}", track.releases.len(), track.track)),
+    );
+
+    // Only used for reading, a failure to delete it just leaves it to expire.
+    let _ =
+        auth.with_token(|token| GooglePlayDataSource::delete_edit(token, &package_name, &edit_id));
+
+    list.check(
+        "Reviews",
+        "Grant the reply to reviews permission to the service account in Play Console",
+        auth.with_token(|token| GooglePlayDataSource::get_reviews(token, &package_name, None))
+            .map(|_| "Readable".to_string()),
+    );
+
+    list.check(
+        "Vitals",
+        "Enable the Google Play Developer Reporting API, and grant the view app information permission",
+        auth.with_scoped_token(&[PLAY_REPORTING_SCOPE], |token| {
+            PlayReportingDataSource::get_metric_set(token, &package_name, "crashRateMetricSet")
+        })
+        .map(|_| "Readable".to_string()),
+    );
+
+    return list.checks;
+}
+
+#[cfg(feature = "ios")]
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    let lengths = [8, 4, 4, 4, 12];
+
+    return groups.len() == lengths.len()
+        && groups.iter().zip(lengths).all(|(group, length)| {
+            group.len() == length && group.chars().all(|c| c.is_ascii_hexdigit())
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(status: CheckStatus) -> Check {
+        return Check {
+            profile: "default".to_string(),
+            platform: "ios",
+            name: "Key",
+            status,
+            detail: String::new(),
+            fix: None,
+        };
+    }
+
+    #[test]
+    fn status_fails_on_any_failed_check() {
+        let checks = [check(CheckStatus::Passed), check(CheckStatus::Failed)];
+
+        assert_eq!(status(&checks), Err("Some checks failed".to_string()));
+    }
+
+    #[test]
+    fn status_fails_when_everything_was_skipped() {
+        assert!(status(&[]).is_err());
+        assert!(status(&[check(CheckStatus::Skipped), check(CheckStatus::Skipped)]).is_err());
+    }
+
+    #[test]
+    fn status_passes_with_a_skipped_platform() {
+        let checks = [check(CheckStatus::Passed), check(CheckStatus::Skipped)];
+
+        assert_eq!(status(&checks), Ok(()));
+    }
+
+    #[cfg(feature = "ios")]
+    #[test]
+    fn is_uuid_checks_the_groups_and_digits() {
+        assert!(is_uuid("69a6de70-03db-47e3-e053-5b8c7c11a4d1"));
+        assert!(is_uuid("69A6DE70-03DB-47E3-E053-5B8C7C11A4D1"));
+        assert!(!is_uuid(""));
+        assert!(!is_uuid("69a6de7003db47e3e0535b8c7c11a4d1"));
+        assert!(!is_uuid("69a6de70-03db-47e3-e053-5b8c7c11a4d"));
+        assert!(!is_uuid("69a6de70-03db-47e3-e053-5b8c7c11a4d1-0"));
+        assert!(!is_uuid("69a6de7-003db-47e3-e053-5b8c7c11a4d1"));
+        assert!(!is_uuid("g9a6de70-03db-47e3-e053-5b8c7c11a4d1"));
+    }
+}
@@ -0,0 +1,383 @@
+use super::key_source::KeySource;
+use super::token_cache::{AccessToken, TokenCache};
+use crate::data::{
+    capture,
+    google_play_datasource::{GooglePlayDataSource, OAuthToken},
+    http, logging,
+};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fs, path::Path};
+
+pub const ANDROID_PUBLISHER_SCOPE: &str = "https://www.googleapis.com/auth/androidpublisher";
+pub const PLAY_REPORTING_SCOPE: &str = "https://www.googleapis.com/auth/playdeveloperreporting";
+
+/// Tokens are refreshed this many seconds before they expire, so that a request
+/// never goes out with a token about to expire.
+const EXPIRY_MARGIN_SECONDS: i64 = 60;
+
+/// The scope of the federated token allowed to impersonate a service account.
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// The Google credential files, told apart by their `type`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Credentials {
+    ServiceAccount(ServiceAccount),
+    AuthorizedUser(AuthorizedUser),
+    ExternalAccount(ExternalAccount),
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+}
+
+/// The user credentials created by `gcloud auth application-default login`.
+#[derive(Debug, Deserialize)]
+struct AuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+}
+
+/// A workload identity federation configuration, exchanging the token of
+/// another identity provider for a Google access token.
+#[derive(Debug, Deserialize)]
+struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+
+    #[serde(default = "default_sts_url")]
+    token_url: String,
+
+    service_account_impersonation_url: Option<String>,
+    credential_source: CredentialSource,
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialSource {
+    file: Option<String>,
+    format: Option<CredentialSourceFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialSourceFormat {
+    #[serde(rename = "type")]
+    _type: String,
+    subject_token_field_name: Option<String>,
+}
+
+fn default_sts_url() -> String {
+    return "https://sts.googleapis.com/v1/token".to_string();
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    iat: usize,
+    exp: usize,
+    aud: String,
+    scope: String,
+}
+
+/// Exchanges the Google credentials for access tokens, one per set of scopes.
+pub struct GoogleAuth {
+    pub key: KeySource,
+    /// The access tokens, keyed by their space separated and sorted scopes.
+    tokens: HashMap<String, AccessToken>,
+    /// Shares the access tokens with the next invocations, when enabled.
+    token_cache: Option<TokenCache>,
+}
+
+impl GoogleAuth {
+    pub fn new(key: KeySource, token_cache: Option<TokenCache>) -> Self {
+        return Self {
+            key,
+            tokens: HashMap::new(),
+            token_cache,
+        };
+    }
+
+    /// Finds the credentials like the Google client libraries: the given key,
+    /// `GOOGLE_APPLICATION_CREDENTIALS`, then the file written by
+    /// `gcloud auth application-default login`.
+    pub fn credentials_source(key: Option<String>) -> Result<KeySource, String> {
+        let gcloud_path = if cfg!(windows) {
+            env::var("APPDATA")
+                .map(|dir| format!("{}/gcloud/application_default_credentials.json", dir))
+        } else {
+            env::var("HOME").map(|dir| {
+                format!(
+                    "{}/.config/gcloud/application_default_credentials.json",
+                    dir
+                )
+            })
+        };
+
+        return key
+            .or_else(|| env::var("GOOGLE_APPLICATION_CREDENTIALS").ok())
+            .or_else(|| gcloud_path.ok().filter(|path| Path::new(path).exists()))
+            .map(KeySource::new)
+            .ok_or("Key path is required, or GOOGLE_APPLICATION_CREDENTIALS".to_string());
+    }
+
+    /// Calls the API with an `androidpublisher` token, see `with_scoped_token`.
+    pub fn with_token<T>(&mut self, call: impl Fn(&str) -> Result<T, String>) -> Result<T, String> {
+        return self.with_scoped_token(&[ANDROID_PUBLISHER_SCOPE], call);
+    }
+
+    /// Calls the API with a token for the scopes, refreshed before it expires. The
+    /// call is made again with a new token only if the API rejected the token on
+    /// its first request, a cached token may have been revoked.
+    pub fn with_scoped_token<T>(
+        &mut self,
+        scopes: &[&str],
+        call: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let token = self.token_for(scopes)?;
+
+        match call(&token) {
+            Err(_) if http::is_rejected_unused(&token) => {
+                self.revoke(scopes)?;
+                return call(&self.token_for(scopes)?);
+            }
+            result => return result,
+        }
+    }
+
+    /// Returns an access token for the scopes, exchanging a new one with the
+    /// credentials the first time a set of scopes is requested or when it is about to expire.
+    pub fn token_for(&mut self, scopes: &[&str]) -> Result<String, String> {
+        let scope = scope_key(scopes);
+
+        if let Some(token) = self.tokens.get(&scope).filter(|t| is_fresh(t)) {
+            return Ok(token.token.clone());
+        }
+
+        let credentials = self.credentials()?;
+        let account = credentials.account();
+        let cached = self
+            .token_cache()
+            .and_then(|cache| cache.get(&account, &scope))
+            .filter(is_fresh);
+
+        let token = match cached {
+            Some(token) => {
+                logging::log(
+                    1,
+                    "auth",
+                    &format!("Using the cached Google token of {} for {}", account, scope),
+                    serde_json::json!({ "account": account, "scope": scope, "cached": true }),
+                );
+                token
+            }
+            None => {
+                logging::log(
+                    1,
+                    "auth",
+                    &format!("Exchanging a Google token for {} with {}", scope, account),
+                    serde_json::json!({ "account": account, "scope": scope, "cached": false }),
+                );
+                let token = credentials.access_token(scopes)?;
+
+                if let Some(cache) = self.token_cache() {
+                    cache.put(&account, &scope, &token)?;
+                }
+
+                token
+            }
+        };
+
+        self.tokens.insert(scope, token.clone());
+
+        return Ok(token.token);
+    }
+
+    /// Forgets the token of the scopes, so that the next call exchanges a new one.
+    pub fn revoke(&mut self, scopes: &[&str]) -> Result<(), String> {
+        let scope = scope_key(scopes);
+        self.tokens.remove(&scope);
+
+        if let Some(cache) = self.token_cache() {
+            cache.remove(&self.credentials()?.account(), &scope)?;
+        }
+
+        return Ok(());
+    }
+
+    /// The token cache, unused while replaying as the replayed tokens are
+    /// redacted.
+    fn token_cache(&self) -> Option<&TokenCache> {
+        return self
+            .token_cache
+            .as_ref()
+            .filter(|_| !capture::is_replaying());
+    }
+
+    /// Parses the credentials and returns the account they belong to.
+    pub fn account(&self) -> Result<String, String> {
+        return self.credentials().map(|c| c.account());
+    }
+
+    fn credentials(&self) -> Result<Credentials, String> {
+        return serde_json::from_str(self.key.read()?)
+            .map_err(|e| format!("Invalid credentials {}: {}", self.key, e));
+    }
+}
+
+impl Credentials {
+    /// Identifies the account in the token cache. The client id of user credentials
+    /// is the one of gcloud for everyone, the user is told by their refresh token.
+    fn account(&self) -> String {
+        match self {
+            Credentials::ServiceAccount(account) => return account.client_email.clone(),
+            Credentials::AuthorizedUser(user) => {
+                let digest = digest::digest(&digest::SHA256, user.refresh_token.as_bytes());
+                let hash: String = digest.as_ref()[..8]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+
+                return format!("user {}", hash);
+            }
+            Credentials::ExternalAccount(account) => {
+                return account
+                    .service_account_impersonation_url
+                    .clone()
+                    .unwrap_or(account.audience.clone())
+            }
+        }
+    }
+
+    fn access_token(&self, scopes: &[&str]) -> Result<AccessToken, String> {
+        match self {
+            Credentials::ServiceAccount(account) => {
+                let private_token = get_private_token(account, &scope_key(scopes))?;
+                let signed_token = GooglePlayDataSource::get_signed_token(&private_token)?;
+                return Ok(access_token(signed_token));
+            }
+
+            // The scopes of user credentials are granted when logging in.
+            Credentials::AuthorizedUser(user) => {
+                return GooglePlayDataSource::refresh_access_token(
+                    &user.client_id,
+                    &user.client_secret,
+                    &user.refresh_token,
+                )
+                .map(access_token);
+            }
+
+            Credentials::ExternalAccount(account) => {
+                let sts_scope = match account.service_account_impersonation_url {
+                    Some(_) => CLOUD_PLATFORM_SCOPE.to_string(),
+                    None => scope_key(scopes),
+                };
+
+                let sts_token = GooglePlayDataSource::exchange_sts_token(
+                    &account.token_url,
+                    &account.audience,
+                    &sts_scope,
+                    &account.credential_source.subject_token()?,
+                    &account.subject_token_type,
+                )?;
+
+                let Some(url) = &account.service_account_impersonation_url else {
+                    return Ok(access_token(sts_token));
+                };
+
+                let token = GooglePlayDataSource::generate_access_token(
+                    &sts_token.access_token,
+                    url,
+                    scopes,
+                )?;
+
+                return Ok(AccessToken {
+                    token: token.access_token,
+                    expires_at: token.expire_time,
+                });
+            }
+        }
+    }
+}
+
+impl CredentialSource {
+    /// Reads the token of the other identity provider, as is or from a JSON field.
+    fn subject_token(&self) -> Result<String, String> {
+        let path = self
+            .file
+            .as_ref()
+            .ok_or("Only file credential sources are supported")?;
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read subject token {}: {}", path, e))?;
+
+        match &self.format {
+            Some(format) if format._type == "json" => {
+                let field = format
+                    .subject_token_field_name
+                    .as_deref()
+                    .ok_or("Missing subject_token_field_name")?;
+                let json: serde_json::Value =
+                    serde_json::from_str(&content).map_err(|e| e.to_string())?;
+
+                return json[field]
+                    .as_str()
+                    .map(|token| token.to_string())
+                    .ok_or(format!("Subject token field not found: {}", field));
+            }
+            _ => return Ok(content.trim().to_string()),
+        }
+    }
+}
+
+fn access_token(token: OAuthToken) -> AccessToken {
+    return AccessToken {
+        token: token.access_token,
+        expires_at: Utc::now() + TimeDelta::seconds(token.expires_in as i64),
+    };
+}
+
+fn get_private_token(service_account: &ServiceAccount, scope: &str) -> Result<String, String> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as usize;
+    let exp = now + 3600;
+
+    let claims = Claims {
+        iss: service_account.client_email.clone(),
+        scope: scope.to_string(),
+        aud: "https://oauth2.googleapis.com/token".to_string(),
+        iat: now,
+        exp,
+    };
+
+    let token = encode(
+        &Header {
+            alg: jsonwebtoken::Algorithm::RS256,
+            ..Default::default()
+        },
+        &claims,
+        &EncodingKey::from_rsa_pem(service_account.private_key.as_bytes())
+            .map_err(|e| format!("Invalid service account private key: {}", e))?,
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(token)
+}
+
+/// Whether the token is still valid past the expiry margin.
+fn is_fresh(token: &AccessToken) -> bool {
+    return token.expires_at > Utc::now() + TimeDelta::seconds(EXPIRY_MARGIN_SECONDS);
+}
+
+fn scope_key(scopes: &[&str]) -> String {
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+
+    return scopes.join(" ");
+}
@@ -1 +1,18 @@
-pub mod store;
\ No newline at end of file
+#[cfg(feature = "ios")]
+pub mod app_store_auth;
+#[cfg(all(feature = "async", any(feature = "android", feature = "ios")))]
+pub mod async_store;
+#[cfg(any(feature = "android", feature = "ios"))]
+pub mod batch;
+#[cfg(any(feature = "android", feature = "ios"))]
+pub mod doctor;
+#[cfg(feature = "android")]
+pub mod google_auth;
+pub mod key_source;
+#[cfg(feature = "android")]
+pub mod rollout;
+pub mod store;
+#[cfg(feature = "android")]
+pub mod token_cache;
+#[cfg(feature = "android")]
+pub mod vitals;
@@ -0,0 +1,585 @@
+use super::{store::GooglePlay, vitals::VersionVitals};
+use crate::data::google_play_datasource::{Release, ReleaseStatus, Track};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// The track operations of an automatic rollout, implemented by `GooglePlay`.
+pub trait RolloutTrack {
+    fn package_name(&self) -> &str;
+
+    fn track(&mut self) -> Result<Track, String>;
+
+    fn vitals(&mut self, days: u64) -> Result<Vec<VersionVitals>, String>;
+
+    fn set_rollout(
+        &mut self,
+        release_name: &str,
+        status: ReleaseStatus,
+        user_fraction: Option<f64>,
+    ) -> Result<(), String>;
+}
+
+impl RolloutTrack for GooglePlay {
+    fn package_name(&self) -> &str {
+        return GooglePlay::package_name(self);
+    }
+
+    fn track(&mut self) -> Result<Track, String> {
+        return GooglePlay::track(self);
+    }
+
+    fn vitals(&mut self, days: u64) -> Result<Vec<VersionVitals>, String> {
+        return GooglePlay::vitals(self, days);
+    }
+
+    fn set_rollout(
+        &mut self,
+        release_name: &str,
+        status: ReleaseStatus,
+        user_fraction: Option<f64>,
+    ) -> Result<(), String> {
+        return GooglePlay::set_rollout(self, release_name, status, user_fraction);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutStatus {
+    InProgress,
+    Halted,
+    Completed,
+}
+
+/// The progress of an automatic rollout, persisted between runs so it can be
+/// driven by a scheduled job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RolloutState {
+    pub package_name: String,
+    pub track: String,
+    pub release: String,
+    pub step: usize,
+    pub updated_at: DateTime<Utc>,
+    pub status: RolloutStatus,
+}
+
+#[derive(Debug)]
+pub enum RolloutOutcome {
+    /// The release was a draft and started at the first step.
+    Started {
+        percent: f64,
+    },
+    Advanced {
+        percent: f64,
+    },
+    Waiting {
+        percent: f64,
+        next_at: Option<DateTime<Utc>>,
+    },
+    /// The interval elapsed, but the vitals of the release do not cover enough
+    /// users yet to advance.
+    AwaitingVitals {
+        percent: f64,
+        users: f64,
+    },
+    Completed,
+    /// The release was halted, because of these metrics and their rate.
+    Halted {
+        exceeded: Vec<(String, f64)>,
+    },
+}
+
+/// Increases the user fraction of a release one step per run, as long as the
+/// vitals of its version codes stay below the thresholds, halting it otherwise.
+pub struct AutoRollout {
+    /// The user percentages, in ascending order.
+    pub steps: Vec<f64>,
+    pub interval: TimeDelta,
+    pub thresholds: Vec<(&'static str, f64)>,
+    pub vitals_days: u64,
+    /// The daily distinct users the vitals must cover on their busiest day
+    /// before advancing, summed over the version codes of the release. Vitals
+    /// arrive a day or two late.
+    pub min_users: f64,
+    /// The release name, defaults to the release in progress or the draft, then
+    /// to the release of the last run or a halted release.
+    pub release: Option<String>,
+    pub state_file: PathBuf,
+}
+
+impl AutoRollout {
+    pub fn run(&self, store: &mut dyn RolloutTrack) -> Result<RolloutOutcome, String> {
+        let track = store.track()?;
+        let now = Utc::now();
+
+        let state = self
+            .load_state()?
+            .filter(|s| s.package_name == store.package_name() && s.track == track.track);
+        let release = self.find_release(&track.releases, state.as_ref())?;
+        let state = state.filter(|s| s.release == release.name);
+
+        let mut state = state.unwrap_or(RolloutState {
+            package_name: store.package_name().to_string(),
+            track: track.track.clone(),
+            release: release.name.clone(),
+            step: self.step_of(release.user_fraction.unwrap_or_default()),
+            updated_at: now,
+            status: RolloutStatus::InProgress,
+        });
+
+        match release.status {
+            ReleaseStatus::Draft => {
+                state.step = 0;
+                self.apply_step(store, &mut state, now)?;
+                return Ok(RolloutOutcome::Started {
+                    percent: self.steps[0],
+                });
+            }
+
+            ReleaseStatus::Completed => {
+                state.status = RolloutStatus::Completed;
+                self.save_state(&state)?;
+                return Ok(RolloutOutcome::Completed);
+            }
+
+            ReleaseStatus::Halted => {
+                state.status = RolloutStatus::Halted;
+                self.save_state(&state)?;
+                return Ok(RolloutOutcome::Halted { exceeded: vec![] });
+            }
+
+            _ => {}
+        }
+
+        // The state is behind the release when it was resumed or changed in the
+        // console, the rollout goes on from its current user fraction.
+        if state.status != RolloutStatus::InProgress {
+            state.status = RolloutStatus::InProgress;
+            state.step = self.step_of(release.user_fraction.unwrap_or_default());
+            state.updated_at = now;
+        }
+
+        let version_codes = release.version_codes.clone().unwrap_or_default();
+        let vitals: Vec<VersionVitals> = store
+            .vitals(self.vitals_days)?
+            .into_iter()
+            .filter(|v| version_codes.contains(&v.version_code))
+            .collect();
+        let users: f64 = vitals.iter().map(|v| v.distinct_users).sum();
+        let exceeded: Vec<(String, f64)> = vitals
+            .iter()
+            .flat_map(|v| v.exceeded(&self.thresholds))
+            .collect();
+
+        if !exceeded.is_empty() {
+            store.set_rollout(&release.name, ReleaseStatus::Halted, release.user_fraction)?;
+            state.status = RolloutStatus::Halted;
+            state.updated_at = now;
+            self.save_state(&state)?;
+            return Ok(RolloutOutcome::Halted { exceeded });
+        }
+
+        let percent = self.steps[state.step];
+        let next_at = state.updated_at + self.interval;
+        let is_last_step = state.step + 1 >= self.steps.len();
+
+        if is_last_step || now < next_at {
+            self.save_state(&state)?;
+            return Ok(RolloutOutcome::Waiting {
+                percent,
+                next_at: Some(next_at).filter(|_| !is_last_step),
+            });
+        }
+
+        if vitals.is_empty() || users < self.min_users {
+            self.save_state(&state)?;
+            return Ok(RolloutOutcome::AwaitingVitals { percent, users });
+        }
+
+        state.step += 1;
+        self.apply_step(store, &mut state, now)?;
+
+        match state.status {
+            RolloutStatus::Completed => return Ok(RolloutOutcome::Completed),
+            _ => {
+                return Ok(RolloutOutcome::Advanced {
+                    percent: self.steps[state.step],
+                })
+            }
+        }
+    }
+
+    /// The release to roll out. Once a rollout halted or completed, it is
+    /// found through the state of the last run, so the outcome is reported
+    /// again rather than failing.
+    fn find_release<'a>(
+        &self,
+        releases: &'a [Release],
+        state: Option<&RolloutState>,
+    ) -> Result<&'a Release, String> {
+        let with_status = |status: ReleaseStatus| releases.iter().find(|r| r.status == status);
+        let release = match &self.release {
+            Some(name) => releases.iter().find(|r| &r.name == name),
+            None => with_status(ReleaseStatus::InProgress)
+                .or_else(|| with_status(ReleaseStatus::Draft))
+                .or_else(|| state.and_then(|s| releases.iter().find(|r| r.name == s.release)))
+                .or_else(|| with_status(ReleaseStatus::Halted)),
+        };
+
+        return release.ok_or("No release in progress, draft or halted release found".to_string());
+    }
+
+    /// The last step reached by a user fraction.
+    fn step_of(&self, user_fraction: f64) -> usize {
+        return self
+            .steps
+            .iter()
+            .rposition(|step| step / 100.0 <= user_fraction + f64::EPSILON)
+            .unwrap_or(0);
+    }
+
+    fn apply_step(
+        &self,
+        store: &mut dyn RolloutTrack,
+        state: &mut RolloutState,
+        now: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let percent = self.steps[state.step];
+
+        if percent >= 100.0 {
+            store.set_rollout(&state.release, ReleaseStatus::Completed, None)?;
+            state.status = RolloutStatus::Completed;
+        } else {
+            store.set_rollout(
+                &state.release,
+                ReleaseStatus::InProgress,
+                Some(percent / 100.0),
+            )?;
+            state.status = RolloutStatus::InProgress;
+        }
+
+        state.updated_at = now;
+
+        return self.save_state(state);
+    }
+
+    fn load_state(&self) -> Result<Option<RolloutState>, String> {
+        if !self.state_file.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&self.state_file).map_err(|e| e.to_string())?;
+
+        return serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid rollout state file: {}", e));
+    }
+
+    /// Writes the state to a temporary file moved over the state file, so a run
+    /// killed midway never leaves a truncated state behind.
+    fn save_state(&self, state: &RolloutState) -> Result<(), String> {
+        let error = |e: std::io::Error| format!("Failed to write rollout state: {}", e);
+        let content = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        let temp_path = self
+            .state_file
+            .with_extension(format!("{}.tmp", std::process::id()));
+
+        fs::write(&temp_path, content).map_err(error)?;
+
+        return fs::rename(&temp_path, &self.state_file).map_err(error);
+    }
+}
+
+/// Parses an interval such as `30m`, `24h` or `2d`.
+pub fn parse_interval(value: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("Invalid interval: {}, expected e.g. 30m, 24h or 2d", value);
+    let unit_index = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let amount: i64 = value[..unit_index].parse().map_err(|_| invalid())?;
+
+    match &value[unit_index..] {
+        "s" => return Ok(TimeDelta::seconds(amount)),
+        "m" => return Ok(TimeDelta::minutes(amount)),
+        "h" => return Ok(TimeDelta::hours(amount)),
+        "d" => return Ok(TimeDelta::days(amount)),
+        _ => return Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::vitals::{ANR_RATE, CRASH_RATE};
+    use std::collections::BTreeMap;
+
+    /// A track of one release, recording the rollout changes.
+    struct StubTrack {
+        releases: Vec<Release>,
+        vitals: Vec<(f64, f64)>,
+        changes: Vec<(ReleaseStatus, Option<f64>)>,
+    }
+
+    impl StubTrack {
+        fn new(status: ReleaseStatus, user_fraction: Option<f64>) -> Self {
+            return Self {
+                releases: vec![Release {
+                    version_codes: Some(vec!["42".to_string()]),
+                    release_notes: None,
+                    status,
+                    name: "1.2.0".to_string(),
+                    user_fraction,
+                    country_targeting: None,
+                    in_app_update_priority: None,
+                }],
+                vitals: vec![],
+                changes: vec![],
+            };
+        }
+
+        /// Reports vitals for the release, with their users and crash rate.
+        fn with_vitals(mut self, users: f64, crash_rate: f64) -> Self {
+            self.vitals.push((users, crash_rate));
+            return self;
+        }
+    }
+
+    impl RolloutTrack for StubTrack {
+        fn package_name(&self) -> &str {
+            return "com.example.app";
+        }
+
+        fn track(&mut self) -> Result<Track, String> {
+            return Ok(Track {
+                track: "production".to_string(),
+                releases: self.releases.clone(),
+            });
+        }
+
+        fn vitals(&mut self, _days: u64) -> Result<Vec<VersionVitals>, String> {
+            return Ok(self
+                .vitals
+                .iter()
+                .map(|(users, crash_rate)| VersionVitals {
+                    version_code: "42".to_string(),
+                    distinct_users: *users,
+                    rates: BTreeMap::from([(CRASH_RATE.to_string(), *crash_rate)]),
+                })
+                .collect());
+        }
+
+        fn set_rollout(
+            &mut self,
+            release_name: &str,
+            status: ReleaseStatus,
+            user_fraction: Option<f64>,
+        ) -> Result<(), String> {
+            let release = self
+                .releases
+                .iter_mut()
+                .find(|r| r.name == release_name)
+                .ok_or("Release not found")?;
+
+            release.status = status;
+            release.user_fraction = user_fraction;
+            self.changes.push((status, user_fraction));
+
+            return Ok(());
+        }
+    }
+
+    fn rollout(name: &str) -> AutoRollout {
+        let state_file = std::env::temp_dir().join(format!(
+            "freitool-rollout-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&state_file);
+
+        return AutoRollout {
+            steps: vec![1.0, 5.0, 20.0, 50.0, 100.0],
+            interval: TimeDelta::hours(24),
+            thresholds: vec![(CRASH_RATE, 0.0109), (ANR_RATE, 0.0047)],
+            vitals_days: 3,
+            min_users: 1000.0,
+            release: None,
+            state_file,
+        };
+    }
+
+    /// Saves the state of a run made `age` ago at `step`.
+    fn save_step(rollout: &AutoRollout, step: usize, age: TimeDelta) {
+        rollout
+            .save_state(&RolloutState {
+                package_name: "com.example.app".to_string(),
+                track: "production".to_string(),
+                release: "1.2.0".to_string(),
+                step,
+                updated_at: Utc::now() - age,
+                status: RolloutStatus::InProgress,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn parse_interval_units() {
+        assert_eq!(parse_interval("45s"), Ok(TimeDelta::seconds(45)));
+        assert_eq!(parse_interval("30m"), Ok(TimeDelta::minutes(30)));
+        assert_eq!(parse_interval("24h"), Ok(TimeDelta::hours(24)));
+        assert_eq!(parse_interval("2d"), Ok(TimeDelta::days(2)));
+    }
+
+    #[test]
+    fn parse_interval_rejects_invalid_values() {
+        for value in ["", "24", "h", "3w", "1.5h", "-1h"] {
+            assert!(parse_interval(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn step_of_is_the_last_step_reached() {
+        let rollout = rollout("step_of");
+
+        assert_eq!(rollout.step_of(0.0), 0);
+        assert_eq!(rollout.step_of(0.01), 0);
+        assert_eq!(rollout.step_of(0.049), 0);
+        assert_eq!(rollout.step_of(0.05), 1);
+        assert_eq!(rollout.step_of(0.07), 1);
+        assert_eq!(rollout.step_of(0.2), 2);
+        assert_eq!(rollout.step_of(1.0), 4);
+    }
+
+    #[test]
+    fn step_of_tolerates_rounding() {
+        let rollout = AutoRollout {
+            steps: vec![10.0, 30.0, 70.0],
+            ..rollout("step_of_rounding")
+        };
+
+        // 0.1 + 0.2 is slightly above 0.3, 0.7 - 0.4 slightly below.
+        assert_eq!(rollout.step_of(0.1 + 0.2), 1);
+        assert_eq!(rollout.step_of(0.7 - 0.4), 1);
+        assert_eq!(rollout.step_of(0.7), 2);
+    }
+
+    #[test]
+    fn run_starts_a_draft_at_the_first_step() {
+        let rollout = rollout("draft");
+        let mut track = StubTrack::new(ReleaseStatus::Draft, None);
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(matches!(outcome, RolloutOutcome::Started { percent } if percent == 1.0));
+        assert_eq!(track.changes, vec![(ReleaseStatus::InProgress, Some(0.01))]);
+    }
+
+    #[test]
+    fn run_halts_on_bad_vitals_before_the_interval_elapsed() {
+        let rollout = rollout("halt");
+        save_step(&rollout, 1, TimeDelta::hours(1));
+        let mut track =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.05)).with_vitals(5000.0, 0.02);
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(
+            matches!(&outcome, RolloutOutcome::Halted { exceeded } if exceeded == &vec![(CRASH_RATE.to_string(), 0.02)])
+        );
+        assert_eq!(track.changes, vec![(ReleaseStatus::Halted, Some(0.05))]);
+        assert_eq!(
+            rollout.load_state().unwrap().map(|s| s.status),
+            Some(RolloutStatus::Halted)
+        );
+    }
+
+    #[test]
+    fn run_finds_the_halted_release_on_the_next_run() {
+        let rollout = rollout("halted");
+        save_step(&rollout, 1, TimeDelta::hours(1));
+        let mut track =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.05)).with_vitals(5000.0, 0.02);
+        rollout.run(&mut track).unwrap();
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(matches!(&outcome, RolloutOutcome::Halted { exceeded } if exceeded.is_empty()));
+        assert_eq!(track.changes.len(), 1);
+    }
+
+    #[test]
+    fn run_finds_a_halted_release_without_state() {
+        let rollout = rollout("halted_without_state");
+        let mut track = StubTrack::new(ReleaseStatus::Halted, Some(0.05));
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(matches!(outcome, RolloutOutcome::Halted { .. }));
+    }
+
+    #[test]
+    fn run_waits_for_the_interval() {
+        let rollout = rollout("wait");
+        save_step(&rollout, 1, TimeDelta::hours(1));
+        let mut track =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.05)).with_vitals(5000.0, 0.001);
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(
+            matches!(outcome, RolloutOutcome::Waiting { percent, next_at: Some(_) } if percent == 5.0)
+        );
+        assert!(track.changes.is_empty());
+    }
+
+    #[test]
+    fn run_waits_for_the_vitals_of_enough_users() {
+        let rollout = rollout("awaiting_vitals");
+        save_step(&rollout, 1, TimeDelta::hours(25));
+        let mut without_vitals = StubTrack::new(ReleaseStatus::InProgress, Some(0.05));
+        let mut few_users =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.05)).with_vitals(999.0, 0.001);
+
+        let without_vitals = rollout.run(&mut without_vitals).unwrap();
+        let few_users = rollout.run(&mut few_users).unwrap();
+
+        assert!(matches!(
+            without_vitals,
+            RolloutOutcome::AwaitingVitals { users, .. } if users == 0.0
+        ));
+        assert!(matches!(
+            few_users,
+            RolloutOutcome::AwaitingVitals { percent, users } if percent == 5.0 && users == 999.0
+        ));
+    }
+
+    #[test]
+    fn run_advances_once_the_interval_elapsed() {
+        let rollout = rollout("advance");
+        save_step(&rollout, 1, TimeDelta::hours(25));
+        let mut track =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.05)).with_vitals(5000.0, 0.001);
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(matches!(outcome, RolloutOutcome::Advanced { percent } if percent == 20.0));
+        assert_eq!(track.changes, vec![(ReleaseStatus::InProgress, Some(0.2))]);
+        assert_eq!(rollout.load_state().unwrap().map(|s| s.step), Some(2));
+    }
+
+    #[test]
+    fn run_completes_at_the_last_step() {
+        let rollout = rollout("complete");
+        save_step(&rollout, 3, TimeDelta::hours(25));
+        let mut track =
+            StubTrack::new(ReleaseStatus::InProgress, Some(0.5)).with_vitals(5000.0, 0.001);
+
+        let outcome = rollout.run(&mut track).unwrap();
+
+        assert!(matches!(outcome, RolloutOutcome::Completed));
+        assert_eq!(track.changes, vec![(ReleaseStatus::Completed, None)]);
+        assert!(matches!(
+            rollout.run(&mut track).unwrap(),
+            RolloutOutcome::Completed
+        ));
+    }
+}
@@ -1,214 +1,682 @@
+#[cfg(feature = "ios")]
+use super::{app_store_auth::AppStoreAuth, key_source::KeySource};
+#[cfg(feature = "android")]
+use super::{
+    google_auth::{GoogleAuth, PLAY_REPORTING_SCOPE},
+    token_cache::TokenCache,
+    vitals::{self, VersionVitals},
+};
+#[cfg(feature = "ios")]
+use crate::config::KeyType;
+#[cfg(feature = "android")]
+use crate::data::google_play_datasource::{
+    CountryTargeting, GooglePlayDataSource, PlayReview, Release, ReleaseNote, ReleaseStatus,
+    Testers, Track, TrackCountryAvailability,
+};
+#[cfg(feature = "ios")]
 use crate::data::{
-    app_store_datasource::AppStoreDataSource,
-    google_play_datasource::{GooglePlayDataSource, Release, ReleaseNote, ReleaseStatus, Track},
+    app_store_datasource::{
+        AnalyticsReport, AnalyticsReportInstance, AnalyticsReportRequest, AppStoreDataSource,
+        AppStoreResource, AppStoreVersionLocalizationResponse, AppStoreVersionResponse,
+        BetaGroupAttributes, BetaTesterAttributes, CustomerReviewsResponse, FinanceReportQuery,
+        SalesReportQuery,
+    },
+    report::Report,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "ios")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
 };
-use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
-use std::{fs, vec};
 
+/// The release operations both stores support.
 pub trait Store {
     fn set_changelog(&mut self, locale: &str, version: &str, changelog: &str)
         -> Result<(), String>;
 
     fn create_version(&mut self, version: &str) -> Result<(), String>;
+
+    /// Lists the customer reviews matching the filter, newest first.
+    fn reviews(&mut self, filter: &ReviewFilter) -> Result<Vec<Review>, String>;
+
+    fn reply_to_review(&mut self, review_id: &str, reply: &str) -> Result<(), String>;
+}
+
+/// A customer review, common to both stores.
+#[derive(Debug, Serialize)]
+pub struct Review {
+    pub id: String,
+    pub rating: u8,
+    pub title: Option<String>,
+    pub body: String,
+    pub author: Option<String>,
+    pub territory: Option<String>,
+    pub language: Option<String>,
+    pub version: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub reply: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReviewFilter {
+    pub ratings: Vec<u8>,
+    pub territory: Option<String>,
+    pub language: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub unanswered: bool,
+}
+
+impl ReviewFilter {
+    /// The territory must be the same, while a language also matches its
+    /// regional variants, `en` matching `en-GB`.
+    pub fn matches(&self, review: &Review) -> bool {
+        let same_territory = match (&self.territory, &review.territory) {
+            (Some(expected), Some(actual)) => actual.eq_ignore_ascii_case(expected),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let same_language = match (&self.language, &review.language) {
+            (Some(expected), Some(actual)) => {
+                let actual = actual.to_lowercase();
+                let expected = expected.to_lowercase();
+                actual == expected
+                    || actual
+                        .strip_prefix(&expected)
+                        .is_some_and(|region| region.starts_with(['-', '_']))
+            }
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        return (self.ratings.is_empty() || self.ratings.contains(&review.rating))
+            && same_territory
+            && same_language
+            && self.since.is_none_or(|since| review.created_at >= since)
+            && self.until.is_none_or(|until| review.created_at < until)
+            && (!self.unanswered || review.reply.is_none());
+    }
 }
 
+/// The key and app of an `AppStore` or `AsyncAppStore`.
+#[cfg(feature = "ios")]
+#[derive(Debug, Default, Clone)]
+pub struct AppStoreSettings {
+    /// The `.p8` key: a path, `env:NAME`, `base64env:NAME` or `-` for stdin.
+    pub key_path: Option<String>,
+    /// Defaults to the ID in the `AuthKey_{ID}.p8` file name.
+    pub key_id: Option<String>,
+    /// Required by team keys, individual keys have none.
+    pub issuer_id: Option<String>,
+    pub key_type: KeyType,
+    pub app_id: Option<String>,
+    /// Required by the sales and finance reports, unused by `AsyncAppStore`.
+    pub vendor_number: Option<String>,
+}
+
+/// An app on App Store Connect, signing its own tokens with an API key.
+#[cfg(feature = "ios")]
 pub struct AppStore {
-    pub key_path: String,
-    pub issuer_id: String,
-    token: Option<String>,
-    token_expiration: Option<usize>,
+    pub vendor_number: Option<String>,
+    auth: AppStoreAuth,
     app_id: String,
 }
 
+#[cfg(feature = "ios")]
 impl AppStore {
-    pub fn new(
-        key_path: Option<String>,
-        issuer_id: Option<String>,
-        app_id: Option<String>,
-    ) -> Result<Self, String> {
-        let key_path = key_path.ok_or("Key path is required")?;
-        let issuer_id = issuer_id.ok_or("Issuer ID is required")?;
-        let app_id = app_id.ok_or("App ID is required")?;
+    /// Fails when the key path or the app ID is missing. The key itself is
+    /// only read on the first call.
+    pub fn new(settings: AppStoreSettings) -> Result<Self, String> {
+        let key_path = settings.key_path.ok_or("Key path is required")?;
+        let app_id = settings.app_id.ok_or("App ID is required")?;
 
         return Ok(Self {
-            key_path,
-            issuer_id,
+            vendor_number: settings.vendor_number,
             app_id,
-            token: None,
-            token_expiration: None,
+            auth: AppStoreAuth::new(
+                KeySource::new(key_path),
+                settings.key_id,
+                settings.issuer_id,
+                settings.key_type,
+            ),
+        });
+    }
+
+    /// Creates or updates the TestFlight "What to Test" notes of a build.
+    pub fn set_beta_changelog(
+        &mut self,
+        locale: &str,
+        build: &str,
+        changelog: &str,
+    ) -> Result<(), String> {
+        return self.auth.with_data_source(|data_source| {
+            let build_id = build_id(data_source, &self.app_id, build)?;
+            let localizations = data_source.get_beta_build_localizations(&build_id)?;
+
+            let localization = localizations
+                .data
+                .iter()
+                .find(|l| l.attributes.locale.to_lowercase() == locale.to_lowercase());
+
+            if let Some(localization) = localization {
+                return data_source.patch_beta_whats_new(&localization.id, changelog);
+            } else {
+                return data_source.create_beta_build_localization(&build_id, locale, changelog);
+            }
         });
     }
 
-    fn token(&mut self) -> Option<String> {
-        if !self.is_logged_in() {
-            match self.login() {
-                Ok(_) => {
-                    return self.token.clone();
+    /// Lists the TestFlight beta groups of the app.
+    pub fn beta_groups(&mut self) -> Result<Vec<AppStoreResource<BetaGroupAttributes>>, String> {
+        return self
+            .auth
+            .with_data_source(|data_source| data_source.get_beta_groups(&self.app_id));
+    }
+
+    /// Makes a build available to the given beta groups, referenced by name or id.
+    pub fn add_build_to_beta_groups(
+        &mut self,
+        build: &str,
+        groups: &[String],
+    ) -> Result<(), String> {
+        return self.auth.with_data_source(|data_source| {
+            let build_id = build_id(data_source, &self.app_id, build)?;
+            let group_ids = beta_group_ids(data_source, &self.app_id, groups)?;
+
+            return data_source.add_build_to_beta_groups(&build_id, &group_ids);
+        });
+    }
+
+    /// Invites the testers to the given beta groups. Every tester is attempted, the
+    /// failures are reported together at the end.
+    pub fn invite_beta_testers(
+        &mut self,
+        testers: &[BetaTesterAttributes],
+        groups: &[String],
+    ) -> Result<(), String> {
+        let group_ids = self
+            .auth
+            .with_data_source(|data_source| beta_group_ids(data_source, &self.app_id, groups))?;
+
+        let errors: Vec<String> = testers
+            .iter()
+            .filter_map(|tester| {
+                self.auth
+                    .with_data_source(|data_source| {
+                        data_source.create_beta_tester(tester, &group_ids)
+                    })
+                    .err()
+                    .map(|e| format!("{}: {}", tester.email.clone().unwrap_or_default(), e))
+            })
+            .collect();
+
+        if errors.is_empty() {
+            return Ok(());
+        } else {
+            return Err(errors.join("\n"));
+        }
+    }
+
+    /// Removes the testers from the given beta groups, or from the app entirely when
+    /// no group is given.
+    pub fn remove_beta_testers(
+        &mut self,
+        emails: &[String],
+        groups: &[String],
+    ) -> Result<(), String> {
+        let group_ids = self
+            .auth
+            .with_data_source(|data_source| beta_group_ids(data_source, &self.app_id, groups))?;
+        let mut errors: Vec<String> = vec![];
+
+        for email in emails {
+            let result = self.auth.with_data_source(|data_source| {
+                let testers = data_source.get_beta_testers(&self.app_id, email)?.data;
+
+                if testers.is_empty() {
+                    return Err("Tester not found".to_string());
                 }
 
-                Err(e) => {
-                    println!("Error: {}", e);
+                for tester in testers {
+                    if group_ids.is_empty() {
+                        data_source.remove_beta_tester_from_app(&tester.id, &self.app_id)?;
+                    } else {
+                        group_ids.iter().try_for_each(|group_id| {
+                            data_source.remove_beta_tester_from_group(group_id, &tester.id)
+                        })?;
+                    }
                 }
+
+                return Ok(());
+            });
+
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", email, e));
             }
         }
 
-        return self.token.clone();
+        if errors.is_empty() {
+            return Ok(());
+        } else {
+            return Err(errors.join("\n"));
+        }
     }
 
-    fn login(&mut self) -> Result<(), String> {
-        let key_id = self
-            .key_path
-            .split('_')
-            .nth(1)
-            .and_then(|s| s.split('.').nth(0));
+    /// Submits a build for beta app review, required before external groups can test it.
+    pub fn submit_for_beta_review(&mut self, build: &str) -> Result<(), String> {
+        return self.auth.with_data_source(|data_source| {
+            let build_id = build_id(data_source, &self.app_id, build)?;
 
-        if key_id.is_none() {
-            return Err("Invalid key path name. It should be AuthKey_{ID}.p8".to_string());
-        }
+            return data_source.submit_for_beta_review(&build_id);
+        });
+    }
+
+    /// Downloads a sales and trends report.
+    pub fn sales_report(&mut self, query: &SalesReportQuery) -> Result<Report, String> {
+        let vendor_number = self.vendor_number()?;
+        return self
+            .auth
+            .with_data_source(|data_source| data_source.get_sales_report(&vendor_number, query));
+    }
+
+    /// Downloads a finance report for a fiscal month.
+    pub fn finance_report(&mut self, query: &FinanceReportQuery) -> Result<Report, String> {
+        let vendor_number = self.vendor_number()?;
+        return self
+            .auth
+            .with_data_source(|data_source| data_source.get_finance_report(&vendor_number, query));
+    }
 
-        let key_id = key_id.unwrap_or_default();
+    /// Returns the analytics report request of the app with the access type,
+    /// creating it when there is none.
+    pub fn analytics_report_request(
+        &mut self,
+        access_type: &str,
+    ) -> Result<AnalyticsReportRequest, String> {
+        return self.auth.with_data_source(|data_source| {
+            let existing = data_source
+                .get_analytics_report_requests(&self.app_id)?
+                .into_iter()
+                .find(|r| {
+                    r.attributes.access_type == access_type
+                        && !r.attributes.stopped_due_to_inactivity
+                });
 
-        println!("Logging in to App Store: {}", key_id);
+            match existing {
+                Some(request) => return Ok(request),
+                None => {
+                    return data_source.create_analytics_report_request(&self.app_id, access_type)
+                }
+            }
+        });
+    }
 
-        let key_string = fs::read_to_string(&self.key_path).map_err(|e| e.to_string())?;
+    /// Lists the analytics reports of every active report request of the app.
+    pub fn analytics_reports(
+        &mut self,
+        category: Option<&str>,
+    ) -> Result<Vec<AnalyticsReport>, String> {
+        return self.auth.with_data_source(|data_source| {
+            let mut reports: Vec<AnalyticsReport> = vec![];
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs() as usize;
+            for request in data_source.get_analytics_report_requests(&self.app_id)? {
+                if request.attributes.stopped_due_to_inactivity {
+                    continue;
+                }
 
-        let expiration = now + 60 * 5;
+                reports.append(&mut data_source.get_analytics_reports(&request.id, category)?);
+            }
 
-        let claims = Claims {
-            iss: self.issuer_id.clone(),
-            iat: now,
-            exp: expiration,
-            aud: "appstoreconnect-v1".to_string(),
-            scope: None,
-        };
+            return Ok(reports);
+        });
+    }
 
-        let token = encode(
-            &Header {
-                alg: jsonwebtoken::Algorithm::ES256,
-                typ: Some("JWT".to_string()),
-                kid: Some(key_id.to_string()),
-                ..Default::default()
-            },
-            &claims,
-            &EncodingKey::from_ec_pem(&key_string.as_str().as_bytes()).unwrap(),
-        )
-        .map_err(|e| e.to_string())?;
-
-        self.token_expiration = Some(expiration);
-        self.token = Some(token);
-
-        Ok(())
-    }
-
-    fn is_logged_in(&self) -> bool {
-        let is_expired: bool = self
-            .token_expiration
-            .map(|exp| {
-                exp < std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_secs() as usize
-            })
-            .unwrap_or(true);
+    pub fn analytics_report_instances(
+        &mut self,
+        report_id: &str,
+        granularity: Option<&str>,
+        processing_date: Option<&str>,
+    ) -> Result<Vec<AnalyticsReportInstance>, String> {
+        return self.auth.with_data_source(|data_source| {
+            data_source.get_analytics_report_instances(report_id, granularity, processing_date)
+        });
+    }
+
+    /// Downloads the segments of every instance of a report to
+    /// `{dir}/{report}/{granularity}/{processing date}/{segment}.csv.gz`. Segments
+    /// already on disk with the expected size are skipped, so an interrupted
+    /// download resumes where it left off when run again.
+    pub fn download_analytics_report(
+        &mut self,
+        report_id: &str,
+        granularity: Option<&str>,
+        processing_date: Option<&str>,
+        dir: &Path,
+    ) -> Result<Vec<SegmentDownload>, String> {
+        let instances = self.analytics_report_instances(report_id, granularity, processing_date)?;
+        let mut downloads: Vec<SegmentDownload> = vec![];
+
+        for instance in instances {
+            let instance_dir = dir
+                .join(report_id)
+                .join(&instance.attributes.granularity)
+                .join(&instance.attributes.processing_date);
+
+            fs::create_dir_all(&instance_dir).map_err(|e| e.to_string())?;
+
+            let segments = self.auth.with_data_source(|data_source| {
+                data_source.get_analytics_report_segments(&instance.id)
+            })?;
+
+            for segment in segments {
+                let path = instance_dir.join(format!("{}.csv.gz", segment.id));
+                let existing_size = fs::metadata(&path).map(|m| m.len()).ok();
+                let is_complete = existing_size
+                    .is_some_and(|size| segment.attributes.size_in_bytes.is_none_or(|s| s == size));
 
-        return self.token.is_some() && !is_expired;
+                if !is_complete {
+                    AppStoreDataSource::download_segment(&segment.attributes.url, &path)?;
+                }
+
+                downloads.push(SegmentDownload {
+                    path,
+                    skipped: is_complete,
+                });
+            }
+        }
+
+        return Ok(downloads);
+    }
+
+    fn vendor_number(&self) -> Result<String, String> {
+        return self
+            .vendor_number
+            .clone()
+            .ok_or("Vendor number is required".to_string());
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct ServiceAccount {
-    client_email: String,
-    private_key: String,
+#[cfg(feature = "ios")]
+fn build_id(data_source: &AppStoreDataSource, app_id: &str, build: &str) -> Result<String, String> {
+    let builds = data_source.get_builds(app_id, build)?;
+
+    match builds.data.as_slice() {
+        [build] => return Ok(build.id.clone()),
+        [] => return Err("Build not found".to_string()),
+        _ => return Err("More than one build found matching the build number".to_string()),
+    }
+}
+
+#[cfg(feature = "ios")]
+fn beta_group_ids(
+    data_source: &AppStoreDataSource,
+    app_id: &str,
+    groups: &[String],
+) -> Result<Vec<String>, String> {
+    if groups.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let beta_groups = data_source.get_beta_groups(app_id)?;
+
+    return groups
+        .iter()
+        .map(|group| {
+            beta_groups
+                .iter()
+                .find(|g| &g.id == group || g.attributes.name.eq_ignore_ascii_case(group))
+                .map(|g| g.id.clone())
+                .ok_or(format!("Beta group not found: {}", group))
+        })
+        .collect();
+}
+
+#[cfg(feature = "ios")]
+#[derive(Debug)]
+pub struct SegmentDownload {
+    pub path: PathBuf,
+    pub skipped: bool,
 }
 
+/// The credentials and app of a `GooglePlay` or `AsyncGooglePlay`.
+#[cfg(feature = "android")]
+#[derive(Debug, Default, Clone)]
+pub struct GooglePlaySettings {
+    /// The service account, external account or authorized user JSON key: a
+    /// path, `env:NAME`, `base64env:NAME` or `-` for stdin. Defaults to
+    /// `GOOGLE_APPLICATION_CREDENTIALS`, then the gcloud default credentials.
+    pub key_path: Option<String>,
+    pub package_name: Option<String>,
+    pub track: Option<String>,
+    /// A file keeping the access tokens between runs.
+    pub token_cache: Option<String>,
+}
+
+/// A package on Google Play, released on `track`. Every change goes through an
+/// edit, committed once complete.
+#[cfg(feature = "android")]
 pub struct GooglePlay {
-    pub key_path: String,
     pub track: String,
-    token: Option<String>,
+    auth: GoogleAuth,
     package_name: String,
 }
 
+#[cfg(feature = "android")]
 impl GooglePlay {
-    pub fn new(key_path: Option<String>, package_name: Option<String>, track: Option<String>) -> Result<Self, String> {
-        let key_path = key_path.ok_or("Key path is required")?;
-        let package_name = package_name.ok_or("Package name is required")?;
-        let track = track.ok_or("Track is required")?;
+    /// Fails when the package name or the track is missing, or when no
+    /// credentials are found. The key itself is only read on the first call.
+    pub fn new(settings: GooglePlaySettings) -> Result<Self, String> {
+        let key = GoogleAuth::credentials_source(settings.key_path)?;
+        let package_name = settings.package_name.ok_or("Package name is required")?;
+        let track = settings.track.ok_or("Track is required")?;
 
         return Ok(Self {
-            key_path,
-            token: None,
+            auth: GoogleAuth::new(key, settings.token_cache.map(TokenCache::new)),
             package_name,
             track,
         });
     }
 
-    fn get_private_token(&self) -> Result<String, String> {
-        let service_account: ServiceAccount = serde_json::from_str(
-            &std::fs::read_to_string(self.key_path.as_str()).map_err(|e| e.to_string())?,
-        )
-        .map_err(|e| e.to_string())?;
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs() as usize;
-        let exp = now + 3600;
-
-        let claims = Claims {
-            iss: service_account.client_email,
-            scope: Some("https://www.googleapis.com/auth/androidpublisher".to_string()),
-            aud: "https://oauth2.googleapis.com/token".to_string(),
-            iat: now,
-            exp,
-        };
+    /// Queries the Android vitals of the last `days` days with data, per version code.
+    pub fn vitals(&mut self, days: u64) -> Result<Vec<VersionVitals>, String> {
+        return self
+            .auth
+            .with_scoped_token(&[PLAY_REPORTING_SCOPE], |token| {
+                vitals::query(token, &self.package_name, days)
+            });
+    }
 
-        let token = encode(
-            &Header {
-                alg: jsonwebtoken::Algorithm::RS256,
-                ..Default::default()
-            },
-            &claims,
-            &EncodingKey::from_rsa_pem(service_account.private_key.as_bytes()).unwrap(),
-        )
-        .map_err(|e| e.to_string())?;
+    pub fn package_name(&self) -> &str {
+        return &self.package_name;
+    }
 
-        Ok(token)
+    /// Reads the track with all its releases.
+    pub fn track(&mut self) -> Result<Track, String> {
+        return self.auth.with_token(|token| {
+            read_edit(token, &self.package_name, |edit_id| {
+                GooglePlayDataSource::get_track(token, &self.package_name, edit_id, &self.track)
+            })
+        });
     }
 
-    fn login(&mut self) -> Result<(), String> {
-        if self.is_logged_in() {
+    /// Changes the status and user fraction of a release. A track has at most one
+    /// release in progress, so rolling out a release replaces the one in progress
+    /// or halted, and completing it also replaces the completed one, like
+    /// `promote`. Halting a release keeps the other releases.
+    pub fn set_rollout(
+        &mut self,
+        release_name: &str,
+        status: ReleaseStatus,
+        user_fraction: Option<f64>,
+    ) -> Result<(), String> {
+        return self.auth.with_token(|token| {
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+            let mut track =
+                GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, &self.track)?;
+
+            let release = track
+                .releases
+                .iter_mut()
+                .find(|r| r.name == release_name)
+                .ok_or("Release not found")?;
+
+            release.status = status;
+            release.user_fraction = user_fraction;
+
+            if matches!(status, ReleaseStatus::InProgress | ReleaseStatus::Completed) {
+                track.releases.retain(|r| {
+                    r.name == release_name
+                        || match r.status {
+                            ReleaseStatus::Draft => true,
+                            ReleaseStatus::Completed => status == ReleaseStatus::InProgress,
+                            _ => false,
+                        }
+                });
+            }
+
+            GooglePlayDataSource::update_track(token, &self.package_name, &edit_id, &track)?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+
             return Ok(());
-        } else {
-            let private_token = self.get_private_token()?;
-            let signed_token = GooglePlayDataSource::get_signed_token(private_token.as_str())?;
-            self.token = Some(signed_token);
+        });
+    }
+
+    /// Copies a release of `from_track` to the track, rolled out to `user_fraction`
+    /// of the users, or completed when none is given. The release is left on
+    /// `from_track`. A track has at most one release in progress, so it
+    /// replaces the one in progress or halted, and a completed release also
+    /// replaces the completed one. Drafts are kept.
+    pub fn promote(
+        &mut self,
+        from_track: &str,
+        release_name: &str,
+        user_fraction: Option<f64>,
+    ) -> Result<(), String> {
+        return self.auth.with_token(|token| {
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+            let source =
+                GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, from_track)?;
+            let mut track =
+                GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, &self.track)?;
+
+            let mut release = source
+                .releases
+                .into_iter()
+                .find(|r| r.name == release_name)
+                .ok_or(format!("Release not found on the {} track", from_track))?;
+
+            release.status = match user_fraction {
+                Some(_) => ReleaseStatus::InProgress,
+                None => ReleaseStatus::Completed,
+            };
+            release.user_fraction = user_fraction;
+
+            track.releases.retain(|r| {
+                r.name != release_name
+                    && match r.status {
+                        ReleaseStatus::Draft => true,
+                        ReleaseStatus::Completed => user_fraction.is_some(),
+                        _ => false,
+                    }
+            });
+            track.releases.push(release);
+
+            GooglePlayDataSource::update_track(token, &self.package_name, &edit_id, &track)?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
 
             return Ok(());
-        }
+        });
     }
 
-    fn is_logged_in(&self) -> bool {
-        return self.token.is_some();
+    /// Lists the Google Groups that can test the track.
+    pub fn testers(&mut self) -> Result<Vec<String>, String> {
+        return self.auth.with_token(|token| {
+            read_edit(token, &self.package_name, |edit_id| {
+                GooglePlayDataSource::get_testers(token, &self.package_name, edit_id, &self.track)
+                    .map(|testers| testers.google_groups)
+            })
+        });
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    iss: String,
-    iat: usize,
-    exp: usize,
-    aud: String,
+    /// Replaces the Google Groups that can test the track.
+    pub fn set_testers(&mut self, google_groups: &[String]) -> Result<(), String> {
+        let testers = Testers {
+            google_groups: google_groups.to_vec(),
+        };
+
+        return self.auth.with_token(|token| {
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+
+            GooglePlayDataSource::update_testers(
+                token,
+                &self.package_name,
+                &edit_id,
+                &self.track,
+                &testers,
+            )?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+
+            return Ok(());
+        });
+    }
+
+    /// Lists the countries where the track is available.
+    pub fn country_availability(&mut self) -> Result<TrackCountryAvailability, String> {
+        return self.auth.with_token(|token| {
+            read_edit(token, &self.package_name, |edit_id| {
+                GooglePlayDataSource::get_country_availability(
+                    token,
+                    &self.package_name,
+                    edit_id,
+                    &self.track,
+                )
+            })
+        });
+    }
+
+    /// Sets the countries of the track. Country availability is read-only in the
+    /// API, so the countries are targeted on every release that is not completed.
+    pub fn set_countries(
+        &mut self,
+        countries: &[String],
+        rest_of_world: bool,
+    ) -> Result<(), String> {
+        return self.auth.with_token(|token| {
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+            let mut track =
+                GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, &self.track)?;
+
+            let mut releases = track
+                .releases
+                .iter_mut()
+                .filter(|r| r.status != ReleaseStatus::Completed)
+                .peekable();
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    scope: Option<String>,
+            if releases.peek().is_none() {
+                return Err(
+                    "No release to target, countries can only be set on a pending release."
+                        .to_string(),
+                );
+            }
+
+            for release in releases {
+                release.country_targeting = Some(CountryTargeting {
+                    countries: countries.iter().map(|c| c.to_uppercase()).collect(),
+                    include_rest_of_world: rest_of_world,
+                });
+            }
+
+            GooglePlayDataSource::update_track(token, &self.package_name, &edit_id, &track)?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+
+            return Ok(());
+        });
+    }
 }
 
+#[cfg(feature = "ios")]
 impl Store for AppStore {
     fn set_changelog(
         &mut self,
@@ -216,47 +684,129 @@ impl Store for AppStore {
         version: &str,
         changelog: &str,
     ) -> Result<(), String> {
-        let token = self.token().ok_or("Not logged in".to_string())?;
-        let data_source = AppStoreDataSource::new(token);
-        let response = data_source.get_app_store_version(&self.app_id, &version);
+        return self.auth.with_data_source(|data_source| {
+            let response = data_source.get_app_store_version(&self.app_id, version)?;
+            let version_id = version_id(&response)?;
+            let localizations = data_source.get_app_version_localizations(&version_id)?;
+            let localization_id = localization_id(&localizations, locale)?;
 
-        if let Ok(response) = response {
-            let has_only_one_element = response.data.len() == 1;
+            return data_source.patch_whats_new(&localization_id, changelog);
+        });
+    }
 
-            if !has_only_one_element {
-                return Err("More than one version found matching the version name".to_string());
-            }
+    fn create_version(&mut self, version: &str) -> Result<(), String> {
+        return self
+            .auth
+            .with_token(|token| AppStoreDataSource::create_version(token, &self.app_id, version));
+    }
+
+    fn reviews(&mut self, filter: &ReviewFilter) -> Result<Vec<Review>, String> {
+        return self
+            .auth
+            .with_data_source(|data_source| reviews(data_source, &self.app_id, filter));
+    }
 
-            let version_id = response.data[0].id.clone();
+    fn reply_to_review(&mut self, review_id: &str, reply: &str) -> Result<(), String> {
+        return self.auth.with_data_source(|data_source| {
+            data_source.create_customer_review_response(review_id, reply)
+        });
+    }
+}
 
-            let localizations = data_source
-                .get_app_version_localizations(&version_id)
-                .map_err(|e| e.to_string())?;
+/// Lists the customer reviews of the app matching the filter, newest first.
+#[cfg(feature = "ios")]
+fn reviews(
+    data_source: &AppStoreDataSource,
+    app_id: &str,
+    filter: &ReviewFilter,
+) -> Result<Vec<Review>, String> {
+    let mut response =
+        data_source.get_customer_reviews(app_id, &filter.ratings, filter.territory.as_deref())?;
+    let mut reviews: Vec<Review> = vec![];
 
-            let localization = localizations.data.iter().find(|l| {
-                l.attributes.locale.to_lowercase() == locale.to_lowercase() || locale.is_empty()
-            });
+    loop {
+        reviews.extend(app_store_reviews(&response)?);
 
-            if let Some(localization) = localization {
-                data_source
-                    .patch_whats_new(&localization.id, changelog)
-                    .map_err(|e| e.to_string())?;
-            } else {
-                return Err("Localization not found".to_string());
-            }
+        // Reviews are sorted by date, so there is no point going past `since`.
+        let is_past_since = filter
+            .since
+            .zip(reviews.last())
+            .is_some_and(|(since, review)| review.created_at < since);
 
-            return Ok(());
-        } else {
-            return Err(response.unwrap_err().to_string());
+        match response.links.next.as_deref() {
+            Some(next) if !is_past_since => {
+                response = data_source.get_next_page::<CustomerReviewsResponse>(next)?;
+            }
+            _ => break,
         }
     }
 
-    fn create_version(&mut self, version: &str) -> Result<(), String> {
-        let token = self.token().ok_or("Not logged in".to_string())?;
-        return AppStoreDataSource::create_version(token.as_str(), &self.app_id, version);
+    reviews.retain(|review| filter.matches(review));
+
+    return Ok(reviews);
+}
+
+/// The id of the only version named `version`.
+#[cfg(feature = "ios")]
+pub(super) fn version_id(response: &AppStoreVersionResponse) -> Result<String, String> {
+    if response.data.len() != 1 {
+        return Err("More than one version found matching the version name".to_string());
     }
+
+    return Ok(response.data[0].id.clone());
+}
+
+/// The id of the version localization in `locale`, or the first one when the
+/// locale is empty.
+#[cfg(feature = "ios")]
+pub(super) fn localization_id(
+    localizations: &AppStoreVersionLocalizationResponse,
+    locale: &str,
+) -> Result<String, String> {
+    return localizations
+        .data
+        .iter()
+        .find(|l| l.attributes.locale.to_lowercase() == locale.to_lowercase() || locale.is_empty())
+        .map(|l| l.id.clone())
+        .ok_or("Localization not found".to_string());
 }
 
+/// The reviews of a page, with their replies.
+#[cfg(feature = "ios")]
+pub(super) fn app_store_reviews(response: &CustomerReviewsResponse) -> Result<Vec<Review>, String> {
+    return response
+        .data
+        .iter()
+        .map(|review| {
+            let reply = review
+                .relationships
+                .response
+                .as_ref()
+                .and_then(|r| r.data.as_ref())
+                .and_then(|data| response.included.iter().find(|i| i.id == data.id))
+                .map(|i| i.attributes.response_body.clone());
+
+            let created_at = DateTime::parse_from_rfc3339(&review.attributes.created_date)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc);
+
+            Ok(Review {
+                id: review.id.clone(),
+                rating: review.attributes.rating,
+                title: review.attributes.title.clone(),
+                body: review.attributes.body.clone().unwrap_or_default(),
+                author: review.attributes.reviewer_nickname.clone(),
+                territory: review.attributes.territory.clone(),
+                language: None,
+                version: None,
+                created_at,
+                reply,
+            })
+        })
+        .collect();
+}
+
+#[cfg(feature = "android")]
 impl Store for GooglePlay {
     fn set_changelog(
         &mut self,
@@ -264,75 +814,301 @@ impl Store for GooglePlay {
         version: &str,
         changelog: &str,
     ) -> Result<(), String> {
-        self.login()?;
+        return self.auth.with_token(|token| {
+            let track_name = &self.track;
+
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+
+            let track = GooglePlayDataSource::get_track(
+                token,
+                &self.package_name,
+                edit_id.as_str(),
+                track_name,
+            )?;
+            let track = changelog_track(&track, version, locale, changelog)?;
 
-        let track_name = &self.track;
-        let token = self.token.as_ref().unwrap();
+            GooglePlayDataSource::update_track(
+                token,
+                &self.package_name,
+                edit_id.as_str(),
+                &track,
+            )?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
 
-        let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+            return Ok(());
+        });
+    }
+
+    fn create_version(&mut self, version: &str) -> Result<(), String> {
+        return self.auth.with_token(|token| {
+            let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+
+            let current_track_data =
+                GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, &self.track)?;
+            let track = draft_track(&current_track_data, version)?;
+
+            GooglePlayDataSource::update_track(
+                token,
+                &self.package_name,
+                edit_id.as_str(),
+                &track,
+            )?;
+            GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+
+            return Ok(());
+        });
+    }
+
+    fn reviews(&mut self, filter: &ReviewFilter) -> Result<Vec<Review>, String> {
+        check_review_filter(filter)?;
+
+        return self.auth.with_token(|token| {
+            let mut page_token: Option<String> = None;
+            let mut reviews: Vec<Review> = vec![];
+
+            loop {
+                let response = GooglePlayDataSource::get_reviews(
+                    token,
+                    &self.package_name,
+                    page_token.as_deref(),
+                )?;
+
+                reviews.extend(google_play_reviews(response.reviews)?);
+
+                page_token = response.token_pagination.and_then(|p| p.next_page_token);
 
-        let track = GooglePlayDataSource::get_track(
-            token,
-            &self.package_name,
-            edit_id.as_str(),
-            track_name,
-        )?;
+                if page_token.is_none() {
+                    break;
+                }
+            }
+
+            reviews.retain(|review| filter.matches(review));
+            reviews.sort_by_key(|review| std::cmp::Reverse(review.created_at));
+
+            return Ok(reviews);
+        });
+    }
+
+    fn reply_to_review(&mut self, review_id: &str, reply: &str) -> Result<(), String> {
+        return self.auth.with_token(|token| {
+            GooglePlayDataSource::reply_to_review(token, &self.package_name, review_id, reply)
+        });
+    }
+}
+
+/// The track with the release notes of the draft release `version` replaced.
+#[cfg(feature = "android")]
+pub(super) fn changelog_track(
+    track: &Track,
+    version: &str,
+    locale: &str,
+    changelog: &str,
+) -> Result<Track, String> {
+    let release = track
+        .releases
+        .iter()
+        .find(|r| r.status == ReleaseStatus::Draft && r.name == version)
+        .ok_or("Release not found or in an uneditable state.")?;
+
+    return Ok(Track {
+        track: track.track.clone(),
+        releases: vec![Release {
+            version_codes: None,
+            status: release.status,
+            name: release.name.clone(),
+            release_notes: Some(vec![ReleaseNote {
+                language: locale.to_string(),
+                text: changelog.to_string(),
+            }]),
+            user_fraction: None,
+            country_targeting: None,
+            in_app_update_priority: None,
+        }],
+    });
+}
+
+/// The track with a new draft release `version`, unless it already exists.
+#[cfg(feature = "android")]
+pub(super) fn draft_track(track: &Track, version: &str) -> Result<Track, String> {
+    if track.releases.iter().any(|r| r.name == version) {
+        return Err("Version already exists".to_string());
+    }
 
-        let release = track
-            .releases
+    return Ok(Track {
+        track: track.track.clone(),
+        releases: vec![Release {
+            version_codes: None,
+            status: ReleaseStatus::Draft,
+            name: version.to_string(),
+            release_notes: None,
+            user_fraction: None,
+            country_targeting: None,
+            in_app_update_priority: None,
+        }],
+    });
+}
+
+/// Google Play reviews have a language but no territory, a territory filter
+/// would drop all of them.
+#[cfg(feature = "android")]
+pub(super) fn check_review_filter(filter: &ReviewFilter) -> Result<(), String> {
+    if filter.territory.is_some() {
+        return Err(
+            "Google Play reviews have no territory, filter them by language instead".to_string(),
+        );
+    }
+
+    return Ok(());
+}
+
+/// Reads from a new edit, deleted afterwards since there is nothing to commit.
+#[cfg(feature = "android")]
+fn read_edit<T>(
+    token: &str,
+    package_name: &str,
+    read: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, String> {
+    let edit_id = GooglePlayDataSource::create_edit_session(token, package_name)?;
+    let result = read(&edit_id);
+    let deleted = GooglePlayDataSource::delete_edit(token, package_name, &edit_id);
+    let value = result?;
+    deleted?;
+
+    return Ok(value);
+}
+
+/// The reviews of a page with a user comment, with their replies.
+#[cfg(feature = "android")]
+pub(super) fn google_play_reviews(reviews: Vec<PlayReview>) -> Result<Vec<Review>, String> {
+    let mut result = vec![];
+
+    for review in reviews {
+        let user_comment = review.comments.iter().find_map(|c| c.user_comment.as_ref());
+        let reply = review
+            .comments
             .iter()
-            .find(|r| r.status == ReleaseStatus::Draft && r.name == version)
-            .ok_or("Release not found or in an uneditable state.")?;
-
-        let track = Track {
-            track: track_name.to_string(),
-            releases: vec![Release {
-                version_codes: None,
-                status: release.status,
-                name: release.name.clone(),
-                release_notes: Some(vec![ReleaseNote {
-                    language: locale.to_string(),
-                    text: changelog.to_string(),
-                }]),
-            }],
+            .find_map(|c| c.developer_comment.as_ref())
+            .map(|c| c.text.clone());
+
+        let Some(user_comment) = user_comment else {
+            continue;
         };
 
-        GooglePlayDataSource::update_track(token, &self.package_name, edit_id.as_str(), track)?;
-        GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+        let seconds: i64 = user_comment
+            .last_modified
+            .seconds
+            .parse()
+            .map_err(|_| "Invalid review timestamp".to_string())?;
 
-        return Ok(());
+        result.push(Review {
+            id: review.review_id.clone(),
+            rating: user_comment.star_rating,
+            title: None,
+            body: user_comment.text.trim().to_string(),
+            author: review.author_name.clone(),
+            territory: None,
+            language: user_comment.reviewer_language.clone(),
+            version: user_comment.app_version_name.clone(),
+            created_at: DateTime::from_timestamp(seconds, user_comment.last_modified.nanos)
+                .ok_or("Invalid review timestamp")?,
+            reply,
+        });
     }
 
-    fn create_version(&mut self, version: &str) -> Result<(), String> {
-        self.login()?;
+    return Ok(result);
+}
 
-        let token = self.token.as_ref().unwrap();
-        let edit_id = GooglePlayDataSource::create_edit_session(token, &self.package_name)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
 
-        let current_track_data =
-            GooglePlayDataSource::get_track(token, &self.package_name, &edit_id, &self.track)?;
+    fn review() -> Review {
+        return Review {
+            id: "1".to_string(),
+            rating: 2,
+            title: None,
+            body: "Crashes on start".to_string(),
+            author: None,
+            territory: Some("USA".to_string()),
+            language: Some("en-GB".to_string()),
+            version: None,
+            created_at: Utc.with_ymd_and_hms(2024, 5, 2, 12, 0, 0).unwrap(),
+            reply: None,
+        };
+    }
 
-        if current_track_data
-            .releases
-            .iter()
-            .any(|r| r.name == version)
-        {
-            return Err("Version already exists".to_string());
-        }
+    #[test]
+    fn review_filter_territory_must_be_the_same() {
+        let filter = |territory: &str| ReviewFilter {
+            territory: Some(territory.to_string()),
+            ..Default::default()
+        };
+
+        assert!(filter("USA").matches(&review()));
+        assert!(filter("usa").matches(&review()));
+        assert!(!filter("US").matches(&review()));
+        assert!(!filter("USA").matches(&Review {
+            territory: None,
+            ..review()
+        }));
+    }
+
+    #[test]
+    fn review_filter_language_matches_its_regions() {
+        let filter = |language: &str| ReviewFilter {
+            language: Some(language.to_string()),
+            ..Default::default()
+        };
+
+        assert!(filter("en").matches(&review()));
+        assert!(filter("EN-gb").matches(&review()));
+        assert!(!filter("en-US").matches(&review()));
+        assert!(!filter("e").matches(&review()));
+        assert!(filter("pt").matches(&Review {
+            language: Some("pt_BR".to_string()),
+            ..review()
+        }));
+    }
 
-        let track = Track {
-            track: self.track.clone(),
-            releases: vec![Release {
-                version_codes: None,
-                status: ReleaseStatus::Draft,
-                name: version.to_string(),
-                release_notes: None,
-            }],
+    #[test]
+    fn review_filter_ratings() {
+        let filter = |ratings: Vec<u8>| ReviewFilter {
+            ratings,
+            ..Default::default()
         };
 
-        GooglePlayDataSource::update_track(token, &self.package_name, edit_id.as_str(), track)?;
-        GooglePlayDataSource::commit_edits(token, &self.package_name, &edit_id)?;
+        assert!(filter(vec![]).matches(&review()));
+        assert!(filter(vec![1, 2]).matches(&review()));
+        assert!(!filter(vec![4, 5]).matches(&review()));
+    }
+
+    #[test]
+    fn review_filter_since_is_inclusive_and_until_exclusive() {
+        let created_at = review().created_at;
+        let filter = |since, until| ReviewFilter {
+            since,
+            until,
+            ..Default::default()
+        };
+
+        assert!(filter(Some(created_at), None).matches(&review()));
+        assert!(!filter(Some(created_at + TimeDelta::seconds(1)), None).matches(&review()));
+        assert!(!filter(None, Some(created_at)).matches(&review()));
+        assert!(filter(None, Some(created_at + TimeDelta::seconds(1))).matches(&review()));
+    }
+
+    #[test]
+    fn review_filter_unanswered() {
+        let filter = ReviewFilter {
+            unanswered: true,
+            ..Default::default()
+        };
 
-        return Ok(());
+        assert!(filter.matches(&review()));
+        assert!(!filter.matches(&Review {
+            reply: Some("Thanks".to_string()),
+            ..review()
+        }));
     }
 }
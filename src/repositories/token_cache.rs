@@ -0,0 +1,207 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Numbers the temporary files, so the threads of a process never share one.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A JSON file holding the access tokens between invocations, keyed by account
+/// and scope. The file is only readable by its owner, as the tokens grant access
+/// to the account.
+pub struct TokenCache {
+    path: PathBuf,
+}
+
+impl TokenCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        return Self { path: path.into() };
+    }
+
+    /// Returns the cached token of the account and scope, if any. Expired tokens
+    /// are left for the caller to replace.
+    pub fn get(&self, account: &str, scope: &str) -> Option<AccessToken> {
+        return self.read().remove(&key(account, scope));
+    }
+
+    pub fn put(&self, account: &str, scope: &str, token: &AccessToken) -> Result<(), String> {
+        let now = Utc::now();
+        let mut tokens = self.read();
+
+        tokens.retain(|_, token| token.expires_at > now);
+        tokens.insert(key(account, scope), token.clone());
+
+        return self.write(&tokens);
+    }
+
+    pub fn remove(&self, account: &str, scope: &str) -> Result<(), String> {
+        let mut tokens = self.read();
+
+        if tokens.remove(&key(account, scope)).is_none() {
+            return Ok(());
+        }
+
+        return self.write(&tokens);
+    }
+
+    /// Reads the cached tokens, an unreadable cache is treated as empty.
+    fn read(&self) -> HashMap<String, AccessToken> {
+        return fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+    }
+
+    /// Writes the tokens to a temporary file created with 0600 permissions and
+    /// moves it over the cache, so a concurrent run never reads a partial file.
+    fn write(&self, tokens: &HashMap<String, AccessToken>) -> Result<(), String> {
+        let error = |e: std::io::Error| format!("Failed to write token cache: {}", e);
+        let content = serde_json::to_string(tokens).map_err(|e| e.to_string())?;
+        let temp_path = self.path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(error)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(&temp_path).map_err(error)?;
+        file.write_all(content.as_bytes()).map_err(error)?;
+
+        return fs::rename(&temp_path, &self.path).map_err(error);
+    }
+}
+
+fn key(account: &str, scope: &str) -> String {
+    return format!("{} {}", account, scope);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn cache(name: &str) -> TokenCache {
+        let dir = std::env::temp_dir().join(format!(
+            "freitool-token-cache-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        return TokenCache::new(dir.join("tokens.json"));
+    }
+
+    fn token(value: &str, expires_in: TimeDelta) -> AccessToken {
+        return AccessToken {
+            token: value.to_string(),
+            expires_at: Utc::now() + expires_in,
+        };
+    }
+
+    #[test]
+    fn put_get_and_remove() {
+        let cache = cache("round_trip");
+
+        assert!(cache.get("a@example.com", "scope").is_none());
+
+        cache
+            .put("a@example.com", "scope", &token("one", TimeDelta::hours(1)))
+            .unwrap();
+        cache
+            .put("a@example.com", "other", &token("two", TimeDelta::hours(1)))
+            .unwrap();
+
+        assert_eq!(
+            cache.get("a@example.com", "scope").map(|t| t.token),
+            Some("one".to_string())
+        );
+        assert!(cache.get("b@example.com", "scope").is_none());
+
+        cache.remove("a@example.com", "scope").unwrap();
+
+        assert!(cache.get("a@example.com", "scope").is_none());
+        assert_eq!(
+            cache.get("a@example.com", "other").map(|t| t.token),
+            Some("two".to_string())
+        );
+    }
+
+    #[test]
+    fn put_drops_the_expired_tokens() {
+        let cache = cache("expiry");
+
+        cache
+            .put(
+                "a@example.com",
+                "scope",
+                &token("old", -TimeDelta::minutes(1)),
+            )
+            .unwrap();
+
+        // An expired token is returned, for the caller to replace.
+        assert_eq!(
+            cache.get("a@example.com", "scope").map(|t| t.token),
+            Some("old".to_string())
+        );
+
+        cache
+            .put("b@example.com", "scope", &token("new", TimeDelta::hours(1)))
+            .unwrap();
+
+        assert!(cache.get("a@example.com", "scope").is_none());
+        assert!(cache.get("b@example.com", "scope").is_some());
+    }
+
+    #[test]
+    fn unreadable_cache_is_empty() {
+        let cache = cache("unreadable");
+        fs::create_dir_all(cache.path.parent().unwrap()).unwrap();
+        fs::write(&cache.path, "not json").unwrap();
+
+        assert!(cache.get("a@example.com", "scope").is_none());
+
+        cache
+            .put("a@example.com", "scope", &token("one", TimeDelta::hours(1)))
+            .unwrap();
+
+        assert!(cache.get("a@example.com", "scope").is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cache_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cache = cache("mode");
+
+        cache
+            .put("a@example.com", "scope", &token("one", TimeDelta::hours(1)))
+            .unwrap();
+
+        let mode = fs::metadata(&cache.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let leftovers = fs::read_dir(cache.path.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+}
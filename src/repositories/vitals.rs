@@ -0,0 +1,281 @@
+use crate::data::play_reporting_datasource::{
+    DateTime, MetricSetQuery, MetricsRow, PlayReportingDataSource,
+};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const CRASH_RATE: &str = "userPerceivedCrashRate";
+pub const ANR_RATE: &str = "userPerceivedAnrRate";
+pub const SLOW_START_RATE: &str = "slowStartRate";
+pub const EXCESSIVE_WAKEUP_RATE: &str = "excessiveWakeupRate";
+
+/// The bad behavior thresholds of the Play Console, as fractions of the users.
+pub const BAD_BEHAVIOR_THRESHOLDS: [(&str, f64); 4] = [
+    (CRASH_RATE, 0.0109),
+    (ANR_RATE, 0.0047),
+    (SLOW_START_RATE, 0.30),
+    (EXCESSIVE_WAKEUP_RATE, 0.10),
+];
+
+const METRIC_SETS: [MetricSetQuery<'static>; 4] = [
+    MetricSetQuery {
+        metric_set: "crashRateMetricSet",
+        metrics: &["crashRate", CRASH_RATE, "distinctUsers"],
+        dimensions: &["versionCode"],
+        filter: None,
+    },
+    MetricSetQuery {
+        metric_set: "anrRateMetricSet",
+        metrics: &["anrRate", ANR_RATE, "distinctUsers"],
+        dimensions: &["versionCode"],
+        filter: None,
+    },
+    MetricSetQuery {
+        metric_set: "slowStartRateMetricSet",
+        metrics: &[SLOW_START_RATE, "distinctUsers"],
+        dimensions: &["versionCode", "startType"],
+        filter: Some("startType = COLD"),
+    },
+    MetricSetQuery {
+        metric_set: "excessiveWakeupRateMetricSet",
+        metrics: &[EXCESSIVE_WAKEUP_RATE, "distinctUsers"],
+        dimensions: &["versionCode"],
+        filter: None,
+    },
+];
+
+/// The vitals of a version code, averaged over the queried days weighted by the
+/// daily distinct users.
+#[derive(Debug, Serialize, Default)]
+pub struct VersionVitals {
+    pub version_code: String,
+    /// The distinct users of the busiest day. The users of several days can't
+    /// be added up, as the returning users would be counted once per day.
+    pub distinct_users: f64,
+    pub rates: BTreeMap<String, f64>,
+}
+
+impl VersionVitals {
+    pub fn rate(&self, metric: &str) -> Option<f64> {
+        return self.rates.get(metric).copied();
+    }
+
+    /// The metrics whose rate is above their threshold, with that rate.
+    pub fn exceeded(&self, thresholds: &[(&str, f64)]) -> Vec<(String, f64)> {
+        return thresholds
+            .iter()
+            .filter_map(|(metric, threshold)| {
+                self.rate(metric)
+                    .filter(|rate| rate > threshold)
+                    .map(|rate| (metric.to_string(), rate))
+            })
+            .collect();
+    }
+}
+
+#[derive(Default)]
+struct Accumulator {
+    distinct_users: f64,
+    weighted_sums: BTreeMap<String, (f64, f64)>,
+}
+
+/// Queries the vitals of the last `days` days for which data is available.
+pub fn query(token: &str, package_name: &str, days: u64) -> Result<Vec<VersionVitals>, String> {
+    let mut versions: BTreeMap<String, Accumulator> = BTreeMap::new();
+
+    for query in METRIC_SETS.iter() {
+        let (start, end) = time_range(token, package_name, query.metric_set, days)?;
+        let rows =
+            PlayReportingDataSource::query_metric_set(token, package_name, query, &start, &end)?;
+
+        for row in rows {
+            accumulate(&mut versions, query, &row);
+        }
+    }
+
+    return Ok(vitals(versions));
+}
+
+/// The vitals of the accumulated versions, the latest version code first.
+fn vitals(versions: BTreeMap<String, Accumulator>) -> Vec<VersionVitals> {
+    let mut vitals: Vec<VersionVitals> = versions
+        .into_iter()
+        .map(|(version_code, accumulator)| VersionVitals {
+            version_code,
+            distinct_users: accumulator.distinct_users,
+            rates: accumulator
+                .weighted_sums
+                .into_iter()
+                .filter(|(_, (_, weight))| *weight > 0.0)
+                .map(|(metric, (sum, weight))| (metric, sum / weight))
+                .collect(),
+        })
+        .collect();
+
+    vitals.sort_by_key(|v| std::cmp::Reverse(v.version_code.parse::<u64>().unwrap_or_default()));
+
+    return vitals;
+}
+
+fn accumulate(
+    versions: &mut BTreeMap<String, Accumulator>,
+    query: &MetricSetQuery,
+    row: &MetricsRow,
+) {
+    let Some(version_code) = row.dimension("versionCode") else {
+        return;
+    };
+
+    let users = row.metric("distinctUsers").unwrap_or_default();
+    let accumulator = versions.entry(version_code).or_default();
+
+    if query.metric_set == METRIC_SETS[0].metric_set {
+        accumulator.distinct_users = accumulator.distinct_users.max(users);
+    }
+
+    for metric in query.metrics.iter().filter(|m| **m != "distinctUsers") {
+        if let Some(rate) = row.metric(metric) {
+            let (sum, weight) = accumulator
+                .weighted_sums
+                .entry(metric.to_string())
+                .or_insert((0.0, 0.0));
+
+            *sum += rate * users;
+            *weight += users;
+        }
+    }
+}
+
+/// The metrics lag a couple of days behind, so the range ends at the latest day
+/// with data rather than today.
+fn time_range(
+    token: &str,
+    package_name: &str,
+    metric_set: &str,
+    days: u64,
+) -> Result<(DateTime, DateTime), String> {
+    let end = PlayReportingDataSource::get_metric_set(token, package_name, metric_set)?
+        .freshness_info
+        .freshnesses
+        .into_iter()
+        .find(|f| f.aggregation_period == "DAILY")
+        .map(|f| f.latest_end_time)
+        .ok_or(format!("No daily data available for {}", metric_set))?;
+
+    let start = NaiveDate::from_ymd_opt(end.year, end.month, end.day)
+        .and_then(|date| date.checked_sub_days(Days::new(days)))
+        .ok_or("Invalid vitals time range")?;
+
+    let start = DateTime {
+        year: start.year(),
+        month: start.month(),
+        day: start.day(),
+        time_zone: end.time_zone.clone(),
+    };
+
+    return Ok((start, end));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(version_code: &str, metrics: &[(&str, f64)]) -> MetricsRow {
+        let metrics: Vec<_> = metrics
+            .iter()
+            .map(|(metric, value)| {
+                json!({ "metric": metric, "decimalValue": { "value": value.to_string() } })
+            })
+            .collect();
+
+        return serde_json::from_value(json!({
+            "dimensions": [{ "dimension": "versionCode", "int64Value": version_code }],
+            "metrics": metrics,
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn accumulate_weights_the_rates_by_the_daily_users() {
+        let mut versions = BTreeMap::new();
+        let crashes = &METRIC_SETS[0];
+
+        accumulate(
+            &mut versions,
+            crashes,
+            &row("42", &[(CRASH_RATE, 0.01), ("distinctUsers", 1000.0)]),
+        );
+        accumulate(
+            &mut versions,
+            crashes,
+            &row("42", &[(CRASH_RATE, 0.04), ("distinctUsers", 3000.0)]),
+        );
+
+        let vitals = vitals(versions);
+        assert_eq!(vitals.len(), 1);
+        assert!((vitals[0].rate(CRASH_RATE).unwrap() - 0.0325).abs() < 1e-12);
+    }
+
+    #[test]
+    fn accumulate_takes_the_users_of_the_busiest_day() {
+        let mut versions = BTreeMap::new();
+
+        for users in [1000.0, 3000.0, 2000.0] {
+            accumulate(
+                &mut versions,
+                &METRIC_SETS[0],
+                &row("42", &[(CRASH_RATE, 0.0), ("distinctUsers", users)]),
+            );
+        }
+
+        // The other metric sets have their own users, which aren't counted again.
+        accumulate(
+            &mut versions,
+            &METRIC_SETS[1],
+            &row("42", &[(ANR_RATE, 0.0), ("distinctUsers", 5000.0)]),
+        );
+
+        assert_eq!(vitals(versions)[0].distinct_users, 3000.0);
+    }
+
+    #[test]
+    fn vitals_are_sorted_latest_version_first_without_empty_rates() {
+        let mut versions = BTreeMap::new();
+
+        accumulate(
+            &mut versions,
+            &METRIC_SETS[0],
+            &row("9", &[(CRASH_RATE, 0.01), ("distinctUsers", 10.0)]),
+        );
+        accumulate(
+            &mut versions,
+            &METRIC_SETS[0],
+            &row("10", &[(CRASH_RATE, 0.02), ("distinctUsers", 0.0)]),
+        );
+
+        let vitals = vitals(versions);
+        let version_codes: Vec<&str> = vitals.iter().map(|v| v.version_code.as_str()).collect();
+        assert_eq!(version_codes, vec!["10", "9"]);
+        assert_eq!(vitals[0].rate(CRASH_RATE), None);
+    }
+
+    #[test]
+    fn exceeded_lists_the_rates_above_the_bad_behavior_thresholds() {
+        let vitals = VersionVitals {
+            version_code: "42".to_string(),
+            distinct_users: 1000.0,
+            rates: BTreeMap::from([
+                (CRASH_RATE.to_string(), 0.02),
+                (ANR_RATE.to_string(), 0.0047),
+                (SLOW_START_RATE.to_string(), 0.10),
+            ]),
+        };
+
+        assert_eq!(
+            vitals.exceeded(&BAD_BEHAVIOR_THRESHOLDS),
+            vec![(CRASH_RATE.to_string(), 0.02)]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Runs the store operations end to end against the mock server, checking the
+//! state it ends up with.
+
+#![allow(clippy::needless_return)]
+
+use freitool::config::{AndroidProfile, Config, IosProfile};
+use freitool::data::endpoints::{set_endpoints, Endpoints};
+use freitool::data::google_play_datasource::{GooglePlayDataSource, Release, ReleaseStatus, Track};
+use freitool::mock_server::MockServer;
+use freitool::repositories::batch::{AppStatus, Batch};
+use freitool::repositories::doctor::{self, Check, CheckStatus};
+use freitool::repositories::store::{
+    AppStore, AppStoreSettings, GooglePlay, GooglePlaySettings, Store,
+};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+const APP_STORE_KEY: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/AuthKey_TEST.p8"
+);
+const SERVICE_ACCOUNT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/service_account.json"
+);
+const ISSUER_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Starts the mock server shared by the tests, the endpoints being set once
+/// per process. Each test uses its own app so they don't see each other.
+fn server() -> &'static str {
+    static URL: OnceLock<String> = OnceLock::new();
+
+    return URL.get_or_init(|| {
+        let url = MockServer::new()
+            .spawn("127.0.0.1:0")
+            .expect("Failed to start the mock server");
+
+        set_endpoints(Endpoints {
+            app_store_connect: format!("{}/v1", url),
+            android_publisher: format!("{}/androidpublisher/v3", url),
+            oauth_token: format!("{}/token", url),
+            play_reporting: format!("{}/playdeveloperreporting/v1beta1", url),
+        });
+
+        return url;
+    });
+}
+
+fn state() -> Value {
+    let body = reqwest::blocking::get(format!("{}/state", server()))
+        .and_then(|response| response.text())
+        .expect("Failed to get the state");
+
+    return serde_json::from_str(&body).expect("Invalid state");
+}
+
+fn google_play(package_name: &str, track: &str) -> GooglePlay {
+    server();
+
+    return GooglePlay::new(GooglePlaySettings {
+        key_path: Some(SERVICE_ACCOUNT.to_string()),
+        package_name: Some(package_name.to_string()),
+        track: Some(track.to_string()),
+        token_cache: None,
+    })
+    .expect("Invalid Google Play settings");
+}
+
+fn app_store(app_id: &str) -> AppStore {
+    server();
+
+    return AppStore::new(AppStoreSettings {
+        key_path: Some(APP_STORE_KEY.to_string()),
+        issuer_id: Some(ISSUER_ID.to_string()),
+        app_id: Some(app_id.to_string()),
+        ..Default::default()
+    })
+    .expect("Invalid App Store settings");
+}
+
+#[test]
+fn google_play_create_version_commits_a_draft() {
+    let mut store = google_play("com.example.create", "beta");
+
+    store.create_version("1.2.0").unwrap();
+
+    let state = state();
+    let track = &state["tracks"]["com.example.create"]["beta"];
+    assert_eq!(track["releases"].as_array().map(|r| r.len()), Some(1));
+    assert_eq!(track["releases"][0]["name"], "1.2.0");
+    assert_eq!(track["releases"][0]["status"], "draft");
+    let open_edits = state["edits"]
+        .as_object()
+        .unwrap()
+        .values()
+        .filter(|edit| edit["packageName"] == "com.example.create")
+        .count();
+    assert_eq!(open_edits, 0);
+}
+
+#[test]
+fn google_play_create_version_rejects_an_existing_version() {
+    let mut store = google_play("com.example.existing", "beta");
+
+    store.create_version("1.2.0").unwrap();
+
+    assert!(store.create_version("1.2.0").is_err());
+}
+
+#[test]
+fn google_play_set_changelog_sets_the_release_notes() {
+    let mut store = google_play("com.example.changelog", "beta");
+
+    store.create_version("1.2.0").unwrap();
+    store.set_changelog("en-US", "1.2.0", "Bug fixes").unwrap();
+
+    let state = state();
+    let release = &state["tracks"]["com.example.changelog"]["beta"]["releases"][0];
+    assert_eq!(
+        release["releaseNotes"],
+        json!([{ "language": "en-US", "text": "Bug fixes" }])
+    );
+}
+
+/// The name and status of the releases of a track, in order.
+fn releases(package_name: &str, track: &str) -> Vec<(String, String)> {
+    return state()["tracks"][package_name][track]["releases"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["name"].as_str().unwrap().to_string(),
+                r["status"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+}
+
+#[test]
+fn google_play_promote_replaces_the_release_in_progress() {
+    let package_name = "com.example.promote";
+    let mut beta = google_play(package_name, "beta");
+    let mut production = google_play(package_name, "production");
+    let release = |name: &str, status: &str| (name.to_string(), status.to_string());
+
+    beta.create_version("1.0.0").unwrap();
+    production.promote("beta", "1.0.0", None).unwrap();
+    beta.create_version("1.1.0").unwrap();
+    production.promote("beta", "1.1.0", Some(0.2)).unwrap();
+    assert_eq!(
+        releases(package_name, "production"),
+        vec![
+            release("1.0.0", "completed"),
+            release("1.1.0", "inProgress")
+        ]
+    );
+
+    beta.create_version("1.2.0").unwrap();
+    production.promote("beta", "1.2.0", Some(0.5)).unwrap();
+    assert_eq!(
+        releases(package_name, "production"),
+        vec![
+            release("1.0.0", "completed"),
+            release("1.2.0", "inProgress")
+        ]
+    );
+
+    production.promote("beta", "1.2.0", None).unwrap();
+    assert_eq!(
+        releases(package_name, "production"),
+        vec![release("1.2.0", "completed")]
+    );
+}
+
+#[test]
+fn app_store_create_version_creates_it_for_the_app() {
+    let mut store = app_store("1000000001");
+
+    store.create_version("2.0.0").unwrap();
+
+    let state = state();
+    let versions: Vec<&Value> = state["appStoreVersions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|v| v["relationships"]["app"]["data"]["id"] == "1000000001")
+        .collect();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0]["attributes"]["versionString"], "2.0.0");
+    assert_eq!(versions[0]["attributes"]["platform"], "IOS");
+}
+
+#[test]
+fn app_store_set_changelog_sets_whats_new() {
+    let mut store = app_store("1000000002");
+
+    store.create_version("2.0.0").unwrap();
+    store
+        .set_changelog("en-US", "2.0.0", "New onboarding")
+        .unwrap();
+
+    let state = state();
+    let version_id = state["appStoreVersions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|v| v["relationships"]["app"]["data"]["id"] == "1000000002")
+        .map(|v| v["id"].clone())
+        .unwrap();
+    let localization = state["appStoreVersionLocalizations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["relationships"]["appStoreVersion"]["data"]["id"] == version_id)
+        .unwrap();
+    assert_eq!(localization["attributes"]["whatsNew"], "New onboarding");
+}
+
+#[test]
+fn app_store_set_changelog_fails_for_a_missing_version() {
+    let mut store = app_store("1000000003");
+
+    assert!(store.set_changelog("en-US", "9.9.9", "Nothing").is_err());
+}
+
+/// The name and status of the checks, in order.
+fn statuses(checks: &[Check]) -> Vec<(&str, CheckStatus)> {
+    return checks.iter().map(|c| (c.name, c.status)).collect();
+}
+
+#[test]
+fn doctor_passes_a_configured_profile() {
+    server();
+
+    let mut checks = doctor::check_ios(
+        "good",
+        &IosProfile {
+            key_path: Some(APP_STORE_KEY.to_string()),
+            issuer_id: Some(ISSUER_ID.to_string()),
+            app_id: Some("1000000004".to_string()),
+            vendor_number: Some("80000000".to_string()),
+            ..Default::default()
+        },
+    );
+    checks.append(&mut doctor::check_android(
+        "good",
+        &AndroidProfile {
+            key_path: Some(SERVICE_ACCOUNT.to_string()),
+            package_name: Some("com.example.doctor".to_string()),
+            ..Default::default()
+        },
+    ));
+
+    let failed: Vec<&Check> = checks
+        .iter()
+        .filter(|c| c.status != CheckStatus::Passed)
+        .collect();
+    assert!(failed.is_empty(), "{:?}", failed);
+    assert_eq!(checks.len(), 14);
+    assert_eq!(doctor::status(&checks), Ok(()));
+}
+
+#[test]
+fn doctor_fails_a_team_key_without_issuer() {
+    server();
+
+    let checks = doctor::check_ios(
+        "bad",
+        &IosProfile {
+            key_path: Some(APP_STORE_KEY.to_string()),
+            app_id: Some("1000000005".to_string()),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        statuses(&checks),
+        vec![
+            ("Key", CheckStatus::Passed),
+            ("Key ID", CheckStatus::Passed),
+            ("Issuer ID", CheckStatus::Failed),
+        ]
+    );
+    assert!(checks[2].fix.is_some());
+    assert!(doctor::status(&checks).is_err());
+}
+
+/// Loads a batch manifest, written to a file of its own.
+fn batch(name: &str, manifest: &str) -> Batch {
+    let path = std::env::temp_dir().join(format!(
+        "freitool-batch-{}-{}.yaml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, manifest).unwrap();
+
+    let batch = Batch::load(path.to_str().unwrap());
+    let _ = std::fs::remove_file(&path);
+
+    return batch.expect("Invalid manifest");
+}
+
+#[test]
+fn batch_reports_partial_failures_in_the_manifest_order() {
+    server();
+    let app = |package_name: &str| {
+        format!(
+            "  - {{ platform: android, package_name: {}, track: beta, key_path: {} }}\n",
+            package_name, SERVICE_ACCOUNT
+        )
+    };
+    let manifest = format!(
+        "operation: create-version\nversion: 1.0.0\nconcurrency: 3\napps:\n{}{}{}{}{}",
+        app("com.example.batch1"),
+        app("com.example.batch2"),
+        "  - { name: broken, platform: android, track: beta }\n",
+        app("com.example.batch3"),
+        "  - { platform: ios, key_path: /missing/AuthKey_X.p8, app_id: '1000000007' }\n",
+    );
+
+    let report = batch("partial", &manifest).run(&Config::default(), None, None);
+
+    let outcomes: Vec<(&str, AppStatus)> = report
+        .apps
+        .iter()
+        .map(|a| (a.app.as_str(), a.status))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            ("com.example.batch1", AppStatus::Succeeded),
+            ("com.example.batch2", AppStatus::Succeeded),
+            ("broken", AppStatus::Failed),
+            ("com.example.batch3", AppStatus::Succeeded),
+            ("1000000007", AppStatus::Failed),
+        ]
+    );
+    assert_eq!((report.succeeded, report.failed), (3, 2));
+    assert_eq!(report.exit_code(), 2);
+    assert_eq!(
+        releases("com.example.batch3", "beta"),
+        vec![("1.0.0".to_string(), "draft".to_string())]
+    );
+}
+
+/// Replaces the releases of a track, any token being accepted by the mock.
+fn set_releases(package_name: &str, track: &str, releases: &[(&str, ReleaseStatus, Option<f64>)]) {
+    server();
+    let track = Track {
+        track: track.to_string(),
+        releases: releases
+            .iter()
+            .map(|(name, status, user_fraction)| Release {
+                version_codes: None,
+                release_notes: None,
+                status: *status,
+                name: name.to_string(),
+                user_fraction: *user_fraction,
+                country_targeting: None,
+                in_app_update_priority: None,
+            })
+            .collect(),
+    };
+
+    let edit_id = GooglePlayDataSource::create_edit_session("token", package_name).unwrap();
+    GooglePlayDataSource::update_track("token", package_name, &edit_id, &track).unwrap();
+    GooglePlayDataSource::commit_edits("token", package_name, &edit_id).unwrap();
+}
+
+#[test]
+fn batch_rollout_replaces_the_release_in_progress() {
+    let package_name = "com.example.batchrollout";
+    let release = |name: &str, status: &str| (name.to_string(), status.to_string());
+    let rollout = |percent: u32| {
+        let manifest = format!(
+            "operation: rollout\nversion: 1.1.0\npercent: {}\napps:\n  \
+             - {{ platform: android, package_name: {}, track: production, key_path: {} }}\n",
+            percent, package_name, SERVICE_ACCOUNT
+        );
+        return batch("rollout", &manifest).run(&Config::default(), None, None);
+    };
+
+    set_releases(
+        package_name,
+        "production",
+        &[
+            ("0.9.0", ReleaseStatus::Completed, None),
+            ("1.0.0", ReleaseStatus::InProgress, Some(0.2)),
+            ("1.1.0", ReleaseStatus::Draft, None),
+        ],
+    );
+
+    assert_eq!(rollout(10).exit_code(), 0);
+    assert_eq!(
+        releases(package_name, "production"),
+        vec![
+            release("0.9.0", "completed"),
+            release("1.1.0", "inProgress")
+        ]
+    );
+
+    assert_eq!(rollout(100).exit_code(), 0);
+    assert_eq!(
+        releases(package_name, "production"),
+        vec![release("1.1.0", "completed")]
+    );
+}
+
+#[cfg(feature = "async")]
+mod async_store {
+    use super::*;
+    use freitool::repositories::async_store::{AsyncAppStore, AsyncGooglePlay, AsyncStore};
+    use std::future::Future;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start the runtime")
+            .block_on(future);
+    }
+
+    #[test]
+    fn google_play_create_version_and_set_changelog() {
+        server();
+        let store = AsyncGooglePlay::new(GooglePlaySettings {
+            key_path: Some(SERVICE_ACCOUNT.to_string()),
+            package_name: Some("com.example.async".to_string()),
+            track: Some("beta".to_string()),
+            token_cache: None,
+        })
+        .unwrap();
+
+        block_on(async {
+            store.create_version("1.2.0").await?;
+            return store.set_changelog("en-US", "1.2.0", "Bug fixes").await;
+        })
+        .unwrap();
+
+        assert_eq!(
+            releases("com.example.async", "beta"),
+            vec![("1.2.0".to_string(), "draft".to_string())]
+        );
+        let state = state();
+        assert_eq!(
+            state["tracks"]["com.example.async"]["beta"]["releases"][0]["releaseNotes"],
+            json!([{ "language": "en-US", "text": "Bug fixes" }])
+        );
+    }
+
+    #[test]
+    fn app_store_create_version_and_set_changelog() {
+        server();
+        let store = AsyncAppStore::new(AppStoreSettings {
+            key_path: Some(APP_STORE_KEY.to_string()),
+            issuer_id: Some(ISSUER_ID.to_string()),
+            app_id: Some("1000000006".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        block_on(async {
+            store.create_version("3.0.0").await?;
+            return store.set_changelog("en-US", "3.0.0", "Async notes").await;
+        })
+        .unwrap();
+
+        let state = state();
+        let version = state["appStoreVersions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|v| v["relationships"]["app"]["data"]["id"] == "1000000006")
+            .unwrap();
+        assert_eq!(version["attributes"]["versionString"], "3.0.0");
+        let localization = state["appStoreVersionLocalizations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["relationships"]["appStoreVersion"]["data"]["id"] == version["id"])
+            .unwrap();
+        assert_eq!(localization["attributes"]["whatsNew"], "Async notes");
+    }
+}
@@ -0,0 +1,116 @@
+//! Replays the data sources against recorded API exchanges, offline.
+//!
+//! The recordings in `fixtures/recordings` were made with `--record` against
+//! `freitool mock-server --address 127.0.0.1:18181`. The capture is set once
+//! per process, so each test replays the exchanges of its own app.
+
+#![allow(clippy::needless_return)]
+
+use freitool::data::app_store_datasource::AppStoreDataSource;
+use freitool::data::capture::{set_capture, Capture};
+use freitool::data::endpoints::{set_endpoints, Endpoints};
+use freitool::data::google_play_datasource::{GooglePlayDataSource, Release, ReleaseStatus, Track};
+use freitool::repositories::store::{GooglePlay, GooglePlaySettings, Store};
+use std::{path::Path, sync::Once};
+
+const RECORDINGS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recordings");
+const SERVICE_ACCOUNT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/service_account.json"
+);
+const RECORDED_URL: &str = "http://127.0.0.1:18181";
+
+fn replay() {
+    static REPLAY: Once = Once::new();
+
+    REPLAY.call_once(|| {
+        set_endpoints(Endpoints {
+            app_store_connect: format!("{}/v1", RECORDED_URL),
+            android_publisher: format!("{}/androidpublisher/v3", RECORDED_URL),
+            oauth_token: format!("{}/token", RECORDED_URL),
+            play_reporting: format!("{}/playdeveloperreporting/v1beta1", RECORDED_URL),
+        });
+        set_capture(Capture::replay(Path::new(RECORDINGS)).expect("Invalid recordings"));
+    });
+}
+
+#[test]
+fn google_play_data_source_replays_a_draft_release() {
+    replay();
+    let package_name = "com.example.app";
+
+    let edit_id = GooglePlayDataSource::create_edit_session("token", package_name).unwrap();
+    assert_eq!(edit_id, "1");
+
+    let track = GooglePlayDataSource::get_track("token", package_name, &edit_id, "beta").unwrap();
+    assert_eq!(track.track, "beta");
+    assert!(track.releases.is_empty());
+
+    let track = Track {
+        track: track.track,
+        releases: vec![Release {
+            version_codes: None,
+            release_notes: None,
+            status: ReleaseStatus::Draft,
+            name: "1.2.0".to_string(),
+            user_fraction: None,
+            country_targeting: None,
+            in_app_update_priority: None,
+        }],
+    };
+    GooglePlayDataSource::update_track("token", package_name, &edit_id, &track).unwrap();
+    GooglePlayDataSource::commit_edits("token", package_name, &edit_id).unwrap();
+}
+
+#[test]
+fn google_play_replay_leaves_the_token_cache_alone() {
+    replay();
+    let dir = std::env::temp_dir().join(format!("freitool-replay-{}", std::process::id()));
+    let token_cache = dir.join("tokens.json");
+
+    let mut store = GooglePlay::new(GooglePlaySettings {
+        key_path: Some(SERVICE_ACCOUNT.to_string()),
+        package_name: Some("com.example.notes".to_string()),
+        track: Some("beta".to_string()),
+        token_cache: Some(token_cache.display().to_string()),
+    })
+    .unwrap();
+
+    store.set_changelog("en-US", "1.3.0", "Bug fixes").unwrap();
+
+    assert!(!token_cache.exists());
+}
+
+#[test]
+fn app_store_data_source_replays_a_version_and_its_notes() {
+    replay();
+    let app_id = "1234567890";
+
+    AppStoreDataSource::create_version("token", app_id, "2.0.0").unwrap();
+
+    let data_source = AppStoreDataSource::new("token".to_string());
+    let versions = data_source.get_app_store_version(app_id, "2.0.0").unwrap();
+    assert_eq!(versions.data.len(), 1);
+
+    let localizations = data_source
+        .get_app_version_localizations(&versions.data[0].id)
+        .unwrap();
+    assert_eq!(localizations.data.len(), 1);
+    assert_eq!(localizations.data[0].attributes.locale, "en-US");
+    assert_eq!(localizations.data[0].attributes.whats_new, None);
+
+    data_source
+        .patch_whats_new(&localizations.data[0].id, "New onboarding")
+        .unwrap();
+}
+
+#[test]
+fn unrecorded_requests_fail_without_reaching_the_network() {
+    replay();
+
+    let error = AppStoreDataSource::new("token".to_string())
+        .get_app("0000000000")
+        .unwrap_err();
+
+    assert!(error.contains("No recorded response left"), "{}", error);
+}